@@ -0,0 +1,40 @@
+// headless comparison of the patched conics with an n-body integration of a settings config.
+//
+// cargo run --example error_analysis -- [config] [--csv] [--duration seconds] [--interval seconds]
+
+use patched_conics_test::loading::SettingsConfigAsset;
+use patched_conics_test::world::{hierarchy_from_settings, run_error_analysis};
+
+// longest step of the n-body integrator
+const MAX_STEP: f64 = 1. / 240.;
+
+fn main() {
+    let mut config_path = "assets/config/settings.config".to_string();
+    let mut csv = false;
+    let mut duration = 60.;
+    let mut sample_interval = 1.;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--csv" => csv = true,
+            "--duration" => duration = args.next().and_then(|v| v.parse().ok()).expect("--duration seconds"),
+            "--interval" => sample_interval = args.next().and_then(|v| v.parse().ok()).expect("--interval seconds"),
+            _ => config_path = arg,
+        }
+    }
+
+    let config = std::fs::read_to_string(&config_path).expect("could not read config");
+    let mut settings: SettingsConfigAsset = ron::de::from_str(&config).expect("could not parse config");
+    settings.load_referenced_files(|path| Ok(std::fs::read(format!("assets/{}", path))?))
+        .expect("could not load the files referenced by the config");
+    let analysis = run_error_analysis(&settings, settings.integrator.unwrap_or_default(),
+        duration, sample_interval, MAX_STEP);
+
+    let hierarchy = hierarchy_from_settings(&settings);
+    if csv {
+        print!("{}", analysis.to_csv(&hierarchy));
+    } else {
+        print!("{}", analysis.to_table(&hierarchy));
+    }
+}
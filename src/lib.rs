@@ -1,16 +1,16 @@
 #![allow(clippy::type_complexity)]
 
 mod camera;
-mod loading;
+pub mod loading;
 mod menu;
 mod overlay_ui;
-mod world;
+pub mod world;
 
 use crate::camera::CameraPlugin;
 use crate::loading::LoadingPlugin;
 use crate::menu::MenuPlugin;
 use crate::overlay_ui::OverlayUiPlugin;
-use crate::world::OrbitsDemoPlugin;
+use crate::world::{GroundTrackMapPlugin,OrbitsDemoPlugin};
 
 use bevy::app::App;
 #[cfg(debug_assertions)]
@@ -40,6 +40,7 @@ impl Plugin for GamePlugin {
             MenuPlugin,
             OverlayUiPlugin,
             OrbitsDemoPlugin,
+            GroundTrackMapPlugin,
         ));
 
         #[cfg(debug_assertions)]
@@ -1,8 +1,9 @@
 use crate::GameState;
+use crate::world::{Ephemeris,EphemerisError,Integrator,SoiModel,Tle,TleError};
 use bevy::{
-    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, ReadAssetBytesError},
     prelude::*,
-    utils::BoxedFuture,
+    utils::{BoxedFuture, HashMap},
 };
 use bevy_asset_loader::prelude::*;
 use serde::Deserialize;
@@ -50,20 +51,145 @@ pub struct TextureAssets {
 
 // Config asset loader
 #[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct ManeuverConfig {
+    pub t: f64,
+    pub prograde: f64,
+    pub normal: f64,
+    pub radial: f64,
+}
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct TransferConfig {
+    // simulation time after which the first burn is placed
+    #[serde(default)]
+    pub after: f64,
+    // radius of the target circular orbit about the initially orbited body
+    #[serde(default)]
+    pub target_radius: Option<f64>,
+    // index of a body orbiting the initially orbited body to meet instead, within bodies
+    #[serde(default)]
+    pub target_body: Option<usize>,
+    // periapsis distance from the target body's centre, passing outside its orbit or inside
+    // when negative, twice its radius when not given
+    #[serde(default)]
+    pub periapsis_offset: Option<f64>,
+    // radius out to which a bi-elliptic transfer goes, a hohmann transfer when not given
+    #[serde(default)]
+    pub bi_elliptic_radius: Option<f64>,
+}
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct EphemerisConfig {
+    // path of a table of states relative to the parent, relative to the assets directory
+    pub path: String,
+    // factor applied to the table's positions and velocities, such as to convert units
+    #[serde(default)]
+    pub scale: Option<f64>,
+    // table read from the file along with the settings
+    #[serde(skip)]
+    pub ephemeris: Option<Ephemeris>,
+}
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct BodyConfig {
+    pub name: String,
+    // index of the parent within bodies, unused for the first (root) body
+    #[serde(default)]
+    pub parent: usize,
+    pub mass: f64,
+    pub radius: f64,
+    #[serde(default)]
+    pub rotation_period: Option<f64>,
+    // oblateness coefficient
+    #[serde(default)]
+    pub j2: f64,
+    // initial state relative to the parent, unused for the root body or with an ephemeris
+    #[serde(default)]
+    pub initial_pos: Vec3,
+    #[serde(default)]
+    pub initial_vel: Vec3,
+    #[serde(default)]
+    pub ephemeris: Option<EphemerisConfig>,
+}
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct TleConfig {
+    // path of a file of two-line element sets, relative to the assets directory
+    pub path: String,
+    // title of the element set within the file, the first when not given
+    #[serde(default)]
+    pub name: Option<String>,
+    // element set read from the file along with the settings
+    #[serde(skip)]
+    pub tle: Option<Tle>,
+}
+#[derive(Asset, TypePath, Debug, Deserialize)]
 pub struct SatelliteConfig {
     pub scale: f32,
+    // index of the body initially orbited within bodies
+    #[serde(default)]
+    pub parent: usize,
+    // initial state relative to the parent, unused when seeded from a tle
+    #[serde(default)]
     pub initial_pos: Vec3,
+    #[serde(default)]
     pub initial_vel: Vec3,
+    #[serde(default)]
+    pub tle: Option<TleConfig>,
+    #[serde(default)]
+    pub maneuver: Option<ManeuverConfig>,
+    // planned transfer, its burns replace the maneuver
+    #[serde(default)]
+    pub transfer: Option<TransferConfig>,
 }
 #[derive(Asset, TypePath, Debug, Deserialize)]
 pub struct SettingsConfigAsset {
     pub mouse_speed: f32,
     pub camera_pos: Vec3,
     pub camera_look_at: Vec3,
-    pub body_scale: f32,
-    pub body_mass: f32,
-    pub moons: Vec<SatelliteConfig>,
+    pub bodies: Vec<BodyConfig>,
     pub satellites: Vec<SatelliteConfig>,
+    // runs a ground-truth n-body simulation of the scenario alongside the patched conics
+    #[serde(default)]
+    pub integrator: Option<Integrator>,
+    // approximation of the bodies' spheres of influence, laplace when not given
+    #[serde(default)]
+    pub soi_model: Option<SoiModel>,
+    // seconds since J2000 at simulation time 0, the epoch of the first satellite tle or else
+    // the start of the first body ephemeris when not given
+    #[serde(default)]
+    pub epoch: Option<f64>,
+}
+
+impl SettingsConfigAsset {
+
+    // paths of the files referenced by the settings, relative to the assets directory
+    pub fn referenced_files(&self) -> Vec<String> {
+        let tle_paths = self.satellites.iter()
+            .filter_map(|sat| sat.tle.as_ref().map(|tle_config| tle_config.path.clone()));
+        let ephemeris_paths = self.bodies.iter()
+            .filter_map(|body| body.ephemeris.as_ref().map(|ephemeris_config| ephemeris_config.path.clone()));
+        tle_paths.chain(ephemeris_paths).collect()
+    }
+
+    // parse the files referenced by the settings, with read giving the contents of each
+    pub fn load_referenced_files(
+        &mut self,
+        mut read: impl FnMut(&str) -> Result<Vec<u8>, SettingsConfigAssetLoaderError>,
+    ) -> Result<(), SettingsConfigAssetLoaderError> {
+        for tle_config in self.satellites.iter_mut().filter_map(|sat| sat.tle.as_mut()) {
+            let text = String::from_utf8(read(&tle_config.path)?)?;
+            let tles = Tle::parse_all(&text)
+                .map_err(|err| SettingsConfigAssetLoaderError::Tle(tle_config.path.clone(), err))?;
+            let tle = tles.into_iter()
+                .find(|tle| tle_config.name.is_none() || tle.name == tle_config.name)
+                .ok_or_else(|| SettingsConfigAssetLoaderError::MissingTle(tle_config.path.clone(), tle_config.name.clone()))?;
+            tle_config.tle = Some(tle);
+        }
+        for ephemeris_config in self.bodies.iter_mut().filter_map(|body| body.ephemeris.as_mut()) {
+            let text = String::from_utf8(read(&ephemeris_config.path)?)?;
+            let ephemeris = Ephemeris::parse(&text)
+                .map_err(|err| SettingsConfigAssetLoaderError::Ephemeris(ephemeris_config.path.clone(), err))?;
+            ephemeris_config.ephemeris = Some(ephemeris);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Default)]
@@ -72,13 +198,28 @@ struct SettingsConfigAssetLoader;
 /// Possible errors that can be produced by [`SettingsConfigAssetLoader`]
 #[non_exhaustive]
 #[derive(Debug, Error)]
-enum SettingsConfigAssetLoaderError {
+pub enum SettingsConfigAssetLoaderError {
     /// An [IO](std::io) Error
     #[error("Could not load asset: {0}")]
     Io(#[from] std::io::Error),
     /// A [RON](ron) Error
     #[error("Could not parse RON: {0}")]
     RonSpannedError(#[from] ron::error::SpannedError),
+    /// A file referenced by the settings could not be read
+    #[error("Could not read referenced file: {0}")]
+    ReadAssetBytes(#[from] ReadAssetBytesError),
+    /// A file referenced by the settings is not UTF-8
+    #[error("Referenced file is not UTF-8: {0}")]
+    Utf8(#[from] std::string::FromUtf8Error),
+    /// A [TLE](Tle) file could not be parsed
+    #[error("{0}: {1}")]
+    Tle(String, TleError),
+    /// A TLE file has no element set of the given name
+    #[error("{0}: no element set {1:?}")]
+    MissingTle(String, Option<String>),
+    /// An [ephemeris](Ephemeris) file could not be parsed
+    #[error("{0}: {1}")]
+    Ephemeris(String, EphemerisError),
 }
 
 impl AssetLoader for SettingsConfigAssetLoader {
@@ -89,12 +230,20 @@ impl AssetLoader for SettingsConfigAssetLoader {
         &'a self,
         reader: &'a mut Reader,
         _settings: &'a (),
-        _load_context: &'a mut LoadContext,
+        load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
         Box::pin(async move {
             let mut bytes = Vec::new();
             reader.read_to_end(&mut bytes).await?;
-            let config_asset = ron::de::from_bytes::<SettingsConfigAsset>(&bytes)?;
+            let mut config_asset = ron::de::from_bytes::<SettingsConfigAsset>(&bytes)?;
+
+            // read the referenced files through the asset server, then parse them once here
+            let mut files = HashMap::new();
+            for path in config_asset.referenced_files() {
+                let file = load_context.read_asset_bytes(path.clone()).await?;
+                files.insert(path, file);
+            }
+            config_asset.load_referenced_files(|path| Ok(files.get(path).cloned().unwrap_or_default()))?;
             Ok(config_asset)
         })
     }
@@ -7,9 +7,24 @@ struct OverlayUi;
 #[derive(Component)]
 pub struct OverlayUiBodyInfo;
 
+#[derive(Component)]
+pub struct OverlayUiTimeWarp;
+
+// time warp buttons, acting as the keyboard's time warp controls
+#[derive(Component, Clone, Copy, PartialEq)]
+pub enum OverlayUiTimeWarpButton {
+    Decrease,
+    Pause,
+    Increase,
+}
+
+// background colors of overlay buttons
+const BUTTON_NORMAL: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED: Color = Color::rgb(0.25, 0.25, 0.25);
+
 #[derive(Default, PartialEq)]
 pub enum ViewingBody {
-    Moon(usize),
+    Body(usize),
     Satellite(usize),
     #[default]
     None,
@@ -18,6 +33,7 @@ pub enum ViewingBody {
 #[derive(Default, Resource)]
 pub struct OverylayUiControls {
     pub viewing_body: ViewingBody,
+    pub show_ground_track: bool, // ground track map of the viewed satellite
 }
 
 pub struct OverlayUiPlugin;
@@ -27,6 +43,7 @@ impl Plugin for OverlayUiPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<OverylayUiControls>();
         app.add_systems(OnEnter(GameState::Playing), setup_overlayui)
+            .add_systems(Update, update_button_colors.run_if(in_state(GameState::Playing)))
             .add_systems(OnExit(GameState::Playing), cleanup_overlayui);
     }
 }
@@ -55,13 +72,61 @@ fn setup_overlayui(
         ))
         .with_children(|parent| {
             parent.spawn(TextBundle::from_section(
-                "Controls:\ntab - next viewing body",
+                "Controls:\ntab - next viewing body\n, / . - decrease / increase time warp\nspace - pause\nm - toggle ground track map",
                 TextStyle {
                     font_size: 16.0,
                     color: Color::rgb(0.9, 0.9, 0.9),
                     ..default()
                 },
             ));
+            parent.spawn((TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 16.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..default()
+                },
+            ), OverlayUiTimeWarp));
+
+            // time warp buttons
+            parent.spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    column_gap: Val::Px(4.),
+                    ..default()
+                },
+                ..default()
+            }).with_children(|parent| {
+                for (button, label) in [
+                    (OverlayUiTimeWarpButton::Decrease, "<<"),
+                    (OverlayUiTimeWarpButton::Pause, "||"),
+                    (OverlayUiTimeWarpButton::Increase, ">>"),
+                ] {
+                    parent.spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(40.),
+                                height: Val::Px(24.),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: BUTTON_NORMAL.into(),
+                            ..default()
+                        },
+                        button,
+                    )).with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font_size: 16.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                                ..default()
+                            },
+                        ));
+                    });
+                }
+            });
         });
 
     // info on current focused object
@@ -105,6 +170,17 @@ fn setup_overlayui(
         });
 }
 
+fn update_button_colors(
+    mut interaction_query: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<OverlayUiTimeWarpButton>)>,
+) {
+    for (interaction, mut color) in &mut interaction_query {
+        *color = match *interaction {
+            Interaction::None => BUTTON_NORMAL.into(),
+            _ => BUTTON_HOVERED.into(),
+        };
+    }
+}
+
 fn cleanup_overlayui(mut commands: Commands, overlayui: Query<Entity, With<OverlayUi>>) {
     for entity in overlayui.iter() {
         commands.entity(entity).despawn_recursive();
@@ -0,0 +1,434 @@
+use bevy::math::DVec3;
+use bevy::prelude::*;
+
+use super::{closest_approach_of, soi_entry_time_of, soi_exit_time, ClosestApproach, Ephemeris, OrbitConic, SoiModel};
+
+// a gravitating body within the hierarchy, positions of each body are relative to its parent
+#[derive(Clone, Debug)]
+pub struct Body {
+    pub name: String,
+    pub parent: Option<usize>, // index of the parent body, none for the root
+    pub mass: f64,
+    pub radius: f64,
+    pub soi_radius: f64, // infinite for the root
+    pub conic: Option<OrbitConic>, // orbit about the parent body, none for the root
+    pub ephemeris: Option<Ephemeris>, // tabulated states relative to the parent, used over the conic within its span
+    pub body_plane_k: DVec3, // normal of the body's plane-of-reference, also its axis of rotation
+    pub rotation_period: f64, // infinite for non-rotating bodies
+    pub j2: f64, // oblateness coefficient, 0. for a spherical body
+}
+
+// tree of gravitating bodies (star -> planets -> moons -> ...), with the root at the origin.
+// parents are always added before their children, so a body's index is greater than its parent's
+#[derive(Clone, Debug, Default, Resource)]
+pub struct BodyHierarchy {
+    pub bodies: Vec<Body>,
+}
+
+impl BodyHierarchy {
+
+    // initializes hierarchy with the root body fixed at the origin
+    pub fn new(
+        name: &str,
+        mass: f64,
+        radius: f64,
+        body_plane_k: DVec3,
+    ) -> Self {
+        BodyHierarchy {
+            bodies: vec![Body {
+                name: name.to_string(),
+                parent: None,
+                mass,
+                radius,
+                soi_radius: f64::INFINITY,
+                conic: None,
+                ephemeris: None,
+                body_plane_k,
+                rotation_period: f64::INFINITY,
+                j2: 0.,
+            }],
+        }
+    }
+
+    // adds body orbiting the given parent, from its initial state relative to the parent,
+    // returns the index of the new body. its orbit is relative to the parent's plane-of-reference
+    // and body_plane_k is the new body's own
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_body(
+        &mut self,
+        name: &str,
+        parent: usize,
+        mass: f64,
+        radius: f64,
+        position: DVec3,
+        velocity: DVec3,
+        body_plane_k: DVec3,
+        soi_model: SoiModel,
+    ) -> usize {
+        let parent_body = &self.bodies[parent];
+        let conic = OrbitConic::from_initial(position, velocity, parent_body.mass, parent_body.body_plane_k);
+        let soi_radius = conic.soi_radius(mass, soi_model).min(parent_body.soi_radius);
+        self.bodies.push(Body {
+            name: name.to_string(),
+            parent: Some(parent),
+            mass,
+            radius,
+            soi_radius,
+            conic: Some(conic),
+            ephemeris: None,
+            body_plane_k,
+            rotation_period: f64::INFINITY,
+            j2: 0.,
+        });
+        self.bodies.len() - 1
+    }
+
+    // adds body orbiting the given parent along an ephemeris, returns the index of the new body.
+    // its conic and soi are fitted to the state at the start of the ephemeris
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_body_with_ephemeris(
+        &mut self,
+        name: &str,
+        parent: usize,
+        mass: f64,
+        radius: f64,
+        ephemeris: Ephemeris,
+        body_plane_k: DVec3,
+        soi_model: SoiModel,
+    ) -> usize {
+        let start = ephemeris.rows[0];
+        let idx = self.add_body(name, parent, mass, radius, start.position, start.velocity, body_plane_k, soi_model);
+        let body = &mut self.bodies[idx];
+        body.conic = body.conic.map(|conic| conic.with_epoch(start.t));
+        body.ephemeris = Some(ephemeris);
+        idx
+    }
+
+    // position and velocity of body relative to its parent at simulation time t, from its
+    // ephemeris within its span, else its conic. outside the span of the ephemeris the body
+    // follows the conic through the nearest end
+    pub fn body_state_at(
+        &self,
+        idx: usize,
+        t: f64,
+    ) -> (DVec3, DVec3) {
+        let body = &self.bodies[idx];
+        match (body.parent, body.conic, &body.ephemeris) {
+            (Some(parent), _, Some(ephemeris)) => ephemeris.state_at(t).unwrap_or_else(|| {
+                let end = if t < ephemeris.start_time() { ephemeris.rows[0] } else { ephemeris.rows[ephemeris.rows.len() - 1] };
+                self.conic_about(parent, end.t, end.position, end.velocity).state_at(t)
+            }),
+            (_, Some(conic), None) => conic.state_at(t),
+            _ => (DVec3::ZERO, DVec3::ZERO),
+        }
+    }
+
+    // conic of body about its parent at simulation time t, osculating when on an ephemeris,
+    // none for the root
+    pub fn body_conic_at(
+        &self,
+        idx: usize,
+        t: f64,
+    ) -> Option<OrbitConic> {
+        let body = &self.bodies[idx];
+        match (body.parent, &body.ephemeris) {
+            (Some(parent), Some(_)) => {
+                let (pos, vel) = self.body_state_at(idx, t);
+                Some(self.conic_about(parent, t, pos, vel))
+            }
+            _ => body.conic,
+        }
+    }
+
+    // indices of the bodies directly orbiting the given body
+    pub fn children(
+        &self,
+        idx: usize,
+    ) -> impl Iterator<Item = usize> + '_ {
+        self.bodies.iter().enumerate()
+            .filter(move |(_, body)| body.parent == Some(idx))
+            .map(|(child_idx, _)| child_idx)
+    }
+
+    // number of levels between the body and the root
+    pub fn depth(
+        &self,
+        idx: usize,
+    ) -> usize {
+        let mut depth = 0;
+        let mut current = idx;
+        while let Some(parent) = self.bodies[current].parent {
+            depth += 1;
+            current = parent;
+        }
+        depth
+    }
+
+    // position of body relative to the root at simulation time t
+    pub fn pos_at_t(
+        &self,
+        idx: usize,
+        t: f64,
+    ) -> DVec3 {
+        let mut pos = DVec3::ZERO;
+        let mut current = idx;
+        while let Some(parent) = self.bodies[current].parent {
+            pos += self.body_state_at(current, t).0;
+            current = parent;
+        }
+        pos
+    }
+
+    // velocity of body relative to the root at simulation time t
+    pub fn vel_at_t(
+        &self,
+        idx: usize,
+        t: f64,
+    ) -> DVec3 {
+        let mut vel = DVec3::ZERO;
+        let mut current = idx;
+        while let Some(parent) = self.bodies[current].parent {
+            vel += self.body_state_at(current, t).1;
+            current = parent;
+        }
+        vel
+    }
+
+    // convert a state relative to one body into a state relative to another, at simulation time t
+    pub fn change_frame(
+        &self,
+        from: usize,
+        to: usize,
+        t: f64,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> (DVec3, DVec3) {
+        if from == to {
+            return (position, velocity);
+        }
+        (position + self.pos_at_t(from, t) - self.pos_at_t(to, t),
+            velocity + self.vel_at_t(from, t) - self.vel_at_t(to, t))
+    }
+
+    // earliest soi change of an object on conic about body idx within [t_start, t_end], as the
+    // exact time of crossing and the index of the new body, either the parent on leaving
+    // the body's soi or a child on entering its soi
+    pub fn next_soi_transition(
+        &self,
+        idx: usize,
+        conic: &OrbitConic,
+        t_start: f64,
+        t_end: f64,
+    ) -> Option<(f64, usize)> {
+        let mut next = self.bodies[idx].parent.and_then(|parent| {
+            soi_exit_time(conic, self.bodies[idx].soi_radius, t_start, t_end).map(|t| (t, parent))
+        });
+        for child in self.children(idx) {
+            // closed conics whose range of distances stays further from a child's than its soi
+            // never enter it, however long the window
+            let soi_radius = self.bodies[child].soi_radius;
+            if let Some(child_conic) = self.bodies[child].conic.filter(|child_conic| conic.e < 1. && child_conic.e < 1.) {
+                let separation = f64::max(conic.periapsis_radius() - child_conic.apoapsis_radius(),
+                    child_conic.periapsis_radius() - conic.apoapsis_radius());
+                if separation > soi_radius {
+                    continue;
+                }
+            }
+            let relative_state = |t: f64| {
+                let (pos, vel) = conic.state_at(t);
+                let (child_pos, child_vel) = self.body_state_at(child, t);
+                (pos - child_pos, vel - child_vel)
+            };
+            // only entries before the earliest change found so far, sampled finely enough for
+            // the faster of the object and the child
+            let t_limit = next.map_or(t_end, |(t, _)| t);
+            let revolution_time = self.body_conic_at(child, t_start)
+                .map_or(f64::INFINITY, |child_conic| child_conic.revolution_time())
+                .min(conic.revolution_time());
+            if let Some(t) = soi_entry_time_of(relative_state, soi_radius, t_start, t_limit, revolution_time) {
+                if !next.is_some_and(|(t_next, _)| t >= t_next) {
+                    next = Some((t, child));
+                }
+            }
+        }
+        next
+    }
+
+    // closest approach within [t_start, t_end] of an object on conic about body idx to the
+    // target body, which need not share the same parent
+    pub fn closest_approach_to_body(
+        &self,
+        idx: usize,
+        conic: &OrbitConic,
+        target: usize,
+        t_start: f64,
+        t_end: f64,
+    ) -> ClosestApproach {
+        let relative_state = |t: f64| {
+            let (pos, vel) = conic.state_at(t);
+            self.change_frame(idx, target, t, pos, vel)
+        };
+        closest_approach_of(relative_state, t_start, t_end)
+    }
+
+    // conic of an object with given state relative to body idx, orbiting that body
+    pub fn conic_about(
+        &self,
+        idx: usize,
+        t: f64,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> OrbitConic {
+        OrbitConic::from_initial(position, velocity, self.bodies[idx].mass, self.bodies[idx].body_plane_k)
+            .with_epoch(t)
+    }
+
+}
+
+// star -> planet -> moon, each on a circular orbit, for the tests of the hierarchy's searches
+#[cfg(test)]
+pub(crate) fn test_hierarchy() -> BodyHierarchy {
+    use crate::world::G;
+    let mut hierarchy = BodyHierarchy::new("Star", 1e20 / G, 1000., DVec3::Z);
+    let planet = hierarchy.add_body("Planet", 0, 1e16 / G, 100.,
+        DVec3::new(1e8, 0., 0.), DVec3::new(0., f64::sqrt(1e20 / 1e8), 0.),
+        DVec3::Z, SoiModel::Laplace);
+    hierarchy.add_body("Moon", planet, 1e12 / G, 10.,
+        DVec3::new(1e5, 0., 0.), DVec3::new(0., f64::sqrt(1e16 / 1e5), 0.),
+        DVec3::Z, SoiModel::Laplace);
+    hierarchy
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{closest_approach, lambert_solver, EphemerisRow, LambertPath, G};
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_body_hierarchy() {
+        let hierarchy = test_hierarchy();
+        assert_eq!(hierarchy.depth(2), 2);
+        assert_eq!(hierarchy.children(0).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(hierarchy.children(1).collect::<Vec<_>>(), vec![2]);
+        assert!(hierarchy.bodies[0].soi_radius.is_infinite());
+        assert!(hierarchy.bodies[2].soi_radius < hierarchy.bodies[1].soi_radius);
+
+        // positions chain through the parents
+        let moon_pos = hierarchy.pos_at_t(2, 0.);
+        assert_f!(moon_pos.x, 1e8 + 1e5);
+        let moon_vel = hierarchy.vel_at_t(2, 0.);
+        assert_f!(moon_vel.y, f64::sqrt(1e12) + f64::sqrt(1e11));
+
+        // state relative to the moon seen from the star, and back down
+        let (pos, vel) = hierarchy.change_frame(2, 0, 0., DVec3::new(50., 0., 0.), DVec3::new(0., 1., 0.));
+        assert_f!(pos.x, 1e8 + 1e5 + 50.);
+        assert_f!(vel.y, f64::sqrt(1e12) + f64::sqrt(1e11) + 1.);
+        let (pos, vel) = hierarchy.change_frame(0, 2, 0., pos, vel);
+        assert_f!(pos.x, 50.);
+        assert_f!(vel.y, 1.);
+    }
+
+    #[test]
+    fn test_next_soi_transition() {
+        let hierarchy = test_hierarchy();
+        let moon_soi = hierarchy.bodies[2].soi_radius;
+
+        // transfer within the planet's soi arriving at the moon's center
+        let moon_conic = hierarchy.bodies[2].conic.unwrap();
+        let r1 = DVec3::new(0., 5e4, 0.);
+        let r2 = moon_conic.pos_at_t(1.);
+        let path = LambertPath::prograde(r1, r2, DVec3::Z);
+        let conic = lambert_solver(r1, r2, 1., path, 0, hierarchy.bodies[1].mass, DVec3::Z)[0];
+        let (t, body) = hierarchy.next_soi_transition(1, &conic, 0., 10.).unwrap();
+        assert_eq!(body, 2);
+        assert!(t < 1.);
+        assert_f!(conic.pos_at_t(t).distance(moon_conic.pos_at_t(t)), moon_soi);
+
+        // fast escape from the planet, leaving its soi
+        let planet_soi = hierarchy.bodies[1].soi_radius;
+        let conic = hierarchy.conic_about(1, 0., DVec3::new(0., 1e4, 0.), DVec3::new(1e5, 2e6, 0.));
+        let (t, body) = hierarchy.next_soi_transition(1, &conic, 0., 1e4).unwrap();
+        assert_eq!(body, 0);
+        assert_f!(conic.pos_at_t(t).length(), planet_soi);
+    }
+
+    #[test]
+    fn test_next_soi_transition_long_window() {
+        let hierarchy = test_hierarchy();
+
+        // ellipse about the planet out to beyond the moon's orbit, entering the moon's soi only
+        // after some revolutions. a window of many revolutions finds the same first entry as a
+        // run of short windows
+        let conic = hierarchy.conic_about(1, 0., DVec3::new(3e4, 0., 0.), DVec3::new(0., 7.25e5, 0.));
+        assert!(conic.apoapsis_radius() > 1e5);
+        for n in 0..10 {
+            let t_start = n as f64 * 0.37;
+            let long = hierarchy.next_soi_transition(1, &conic, t_start, t_start + 1000.).unwrap();
+            let short = (0..100).find_map(|k| {
+                let t = t_start + k as f64 * 10.;
+                hierarchy.next_soi_transition(1, &conic, t, t + 10.)
+            }).unwrap();
+            assert_eq!(long.1, short.1);
+            assert!(f64::abs(long.0 - short.0) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_closest_approach_to_body() {
+        let hierarchy = test_hierarchy();
+        let moon_conic = hierarchy.bodies[2].conic.unwrap();
+
+        // about the star, passing 100 km from the moon
+        let r2 = hierarchy.pos_at_t(2, 1000.) + DVec3::new(0., 0., 100.);
+        let r1 = DVec3::new(0., 1e8, 0.);
+        let path = LambertPath::prograde(r1, r2, DVec3::Z);
+        let conic = lambert_solver(r1, r2, 1000., path, 0, hierarchy.bodies[0].mass, DVec3::Z)[0];
+        let approach = hierarchy.closest_approach_to_body(0, &conic, 2, 0., 2000.);
+        assert!(approach.distance <= 100.);
+        let moon_pos = hierarchy.pos_at_t(2, approach.t);
+        assert_f!(approach.distance, conic.pos_at_t(approach.t).distance(moon_pos));
+
+        // same result from the moon's parent frame as from its own conic
+        let (pos, vel) = hierarchy.change_frame(0, 1, approach.t, conic.pos_at_t(approach.t), conic.vel_at_t(approach.t));
+        let planet_conic = hierarchy.conic_about(1, approach.t, pos, vel);
+        let planet_approach = closest_approach(&planet_conic, &moon_conic, approach.t - 1., approach.t + 1.);
+        assert!(planet_approach.distance <= approach.distance * 1.01);
+    }
+
+    #[test]
+    fn test_ephemeris_body() {
+        let mut hierarchy = test_hierarchy();
+        let moon_conic = hierarchy.bodies[2].conic.unwrap();
+
+        // second moon along a table sampled from the first moon's orbit, offset by half a period
+        let rows = (0..=20).map(|n| {
+            let t = 100. + n as f64 * moon_conic.period / 40.;
+            let (position, velocity) = moon_conic.state_at(t + moon_conic.period / 2.);
+            EphemerisRow { t, position, velocity }
+        }).collect();
+        let moon2 = hierarchy.add_body_with_ephemeris("Moon2", 1, 1e12 / G, 10.,
+            Ephemeris { rows }, DVec3::Z, SoiModel::Laplace);
+        assert_f!(hierarchy.bodies[moon2].soi_radius, hierarchy.bodies[2].soi_radius);
+
+        // follows the table within its span, and the conic through its ends outside
+        for t in [150., moon_conic.period / 4., 0., moon_conic.period] {
+            let (pos, vel) = hierarchy.body_state_at(moon2, t);
+            let (expected_pos, expected_vel) = moon_conic.state_at(t + moon_conic.period / 2.);
+            assert!(pos.distance(expected_pos) < 1e-6 * expected_pos.length());
+            assert!(vel.distance(expected_vel) < 1e-5 * expected_vel.length());
+            assert_f!(hierarchy.pos_at_t(moon2, t).x, (hierarchy.pos_at_t(1, t) + pos).x);
+        }
+        let osculating = hierarchy.body_conic_at(moon2, 200.).unwrap();
+        assert_f!(osculating.h, moon_conic.h);
+    }
+}
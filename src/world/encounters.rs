@@ -0,0 +1,289 @@
+use bevy::math::DVec3;
+
+use super::{bracketed_newton_solver, OrbitConic};
+
+// samples of the search window, each interval is checked for a crossing or for a closest
+// approach within it, so only two closest approaches within one interval can be missed
+const CROSSING_SAMPLES: u32 = 256;
+// least samples per revolution of the objects in a crossing search, so long windows are
+// sampled more densely rather than stepping over whole orbits
+const SAMPLES_PER_REVOLUTION: f64 = 32.;
+// bisection iterations locating a closest approach within an interval
+const MAX_BISECTION_ITERATIONS: u32 = 100;
+// distance from the boundary, relative to the radius, treated as on the boundary when a
+// search starts, so a search starting at a crossing does not find that same crossing
+const BOUNDARY_EPSILON: f64 = 1e-6;
+
+// closest approach between two objects within a search window
+#[derive(Clone, Copy, Debug)]
+pub struct ClosestApproach {
+    pub t: f64, // simulation time of closest approach
+    pub distance: f64,
+    pub relative_velocity: DVec3, // velocity of the first object relative to the second
+}
+
+// closest approach within [t_start, t_end] between objects on two conics about the same body
+pub fn closest_approach(
+    conic: &OrbitConic,
+    other: &OrbitConic,
+    t_start: f64,
+    t_end: f64,
+) -> ClosestApproach {
+    let relative_state = |t: f64| {
+        let (pos, vel) = conic.state_at(t);
+        let (other_pos, other_vel) = other.state_at(t);
+        (pos - other_pos, vel - other_vel)
+    };
+    closest_approach_of(relative_state, t_start, t_end)
+}
+
+// closest approach within [t_start, t_end] of a relative state, the nearer of the window's
+// ends and the minima of distance found where the range rate turns from closing to opening
+pub fn closest_approach_of(
+    relative_state: impl Fn(f64) -> (DVec3, DVec3),
+    t_start: f64,
+    t_end: f64,
+) -> ClosestApproach {
+    let approach_at = |t: f64| {
+        let (pos, vel) = relative_state(t);
+        ClosestApproach { t, distance: pos.length(), relative_velocity: vel }
+    };
+    let range_rate = |t: f64| {
+        let (pos, vel) = relative_state(t);
+        pos.dot(vel) / pos.length()
+    };
+
+    let mut closest = approach_at(t_start);
+    let end = approach_at(t_end);
+    if end.distance < closest.distance {
+        closest = end;
+    }
+    let step = (t_end - t_start) / CROSSING_SAMPLES as f64;
+    for n in 0..CROSSING_SAMPLES {
+        let t_lo = t_start + n as f64 * step;
+        let t_hi = t_start + (n + 1) as f64 * step;
+        if range_rate(t_lo) < 0. && range_rate(t_hi) > 0. {
+            let approach = approach_at(bisect_sign_change(&range_rate, t_lo, t_hi));
+            if approach.distance < closest.distance {
+                closest = approach;
+            }
+        }
+    }
+    closest
+}
+
+// first time within [t_start, t_end] that an object on conic enters the sphere of given radius
+// about an object on other, both conics about the same body. t_start if already inside
+pub fn soi_entry_time(
+    conic: &OrbitConic,
+    other: &OrbitConic,
+    soi_radius: f64,
+    t_start: f64,
+    t_end: f64,
+) -> Option<f64> {
+    let relative_state = |t: f64| {
+        let (pos, vel) = conic.state_at(t);
+        let (other_pos, other_vel) = other.state_at(t);
+        (pos - other_pos, vel - other_vel)
+    };
+    let revolution_time = conic.revolution_time().min(other.revolution_time());
+    soi_entry_time_of(relative_state, soi_radius, t_start, t_end, revolution_time)
+}
+
+// first time within [t_start, t_end] that a relative state enters the sphere of given radius,
+// t_start if already inside. revolution_time is the shortest time scale of the motion, such as
+// the shorter of the objects' revolution times
+pub fn soi_entry_time_of(
+    relative_state: impl Fn(f64) -> (DVec3, DVec3),
+    soi_radius: f64,
+    t_start: f64,
+    t_end: f64,
+    revolution_time: f64,
+) -> Option<f64> {
+    first_crossing(relative_state, soi_radius, true, t_start, t_end, revolution_time)
+}
+
+// first time within [t_start, t_end] that an object on conic leaves the sphere of given
+// radius about its body. t_start if already outside
+pub fn soi_exit_time(
+    conic: &OrbitConic,
+    soi_radius: f64,
+    t_start: f64,
+    t_end: f64,
+) -> Option<f64> {
+    // closed conics within the sphere never leave it, however long the window
+    if conic.e < 1. && conic.apoapsis_radius() < soi_radius {
+        return None;
+    }
+    first_crossing(|t| conic.state_at(t), soi_radius, false, t_start, t_end, conic.revolution_time())
+}
+
+// first time the distance of a relative state crosses the radius, inwards when entering
+// else outwards, by bracketing between samples and refining with newton's method. samples are
+// no further apart than a fraction of revolution_time, however long the window
+fn first_crossing(
+    relative_state: impl Fn(f64) -> (DVec3, DVec3),
+    radius: f64,
+    entering: bool,
+    t_start: f64,
+    t_end: f64,
+    revolution_time: f64,
+) -> Option<f64> {
+    if t_end < t_start {
+        return None;
+    }
+    let sign = if entering { -1. } else { 1. };
+    // positive once across the boundary, and its rate of change
+    let g = |t: f64| sign * (relative_state(t).0.length() - radius);
+    let dg = |t: f64| {
+        let (pos, vel) = relative_state(t);
+        sign * pos.dot(vel) / pos.length()
+    };
+
+    if g(t_start) > BOUNDARY_EPSILON * radius {
+        return Some(t_start);
+    }
+    let samples = f64::max(CROSSING_SAMPLES as f64,
+        ((t_end - t_start) / revolution_time * SAMPLES_PER_REVOLUTION).ceil()) as u64;
+    let step = (t_end - t_start) / samples as f64;
+    for n in 0..samples {
+        let t_lo = t_start + n as f64 * step;
+        let t_hi = t_start + (n + 1) as f64 * step;
+        // crossing between samples
+        if g(t_hi) > 0. {
+            return Some(bracketed_newton_solver(g, dg, t_lo, t_hi, t_lo));
+        }
+        // closest approach between samples which may cross and return
+        if dg(t_lo) > 0. && dg(t_hi) < 0. {
+            let t_peak = bisect_sign_change(&dg, t_lo, t_hi);
+            if g(t_peak) > 0. {
+                return Some(bracketed_newton_solver(g, dg, t_lo, t_peak, t_lo));
+            }
+        }
+    }
+    None
+}
+
+// find where f changes sign within [x_lo, x_hi] by bisection, also used to find a closest approach
+fn bisect_sign_change(
+    f: &impl Fn(f64) -> f64,
+    x_lo: f64,
+    x_hi: f64,
+) -> f64 {
+    let lo_sign = f(x_lo).signum();
+    let mut lo = x_lo;
+    let mut hi = x_hi;
+    for _ in 0..MAX_BISECTION_ITERATIONS {
+        let mid = (lo + hi) / 2.;
+        if mid <= lo || mid >= hi {
+            break;
+        }
+        if f(mid).signum() == lo_sign {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo + hi) / 2.
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{lambert_solver, LambertPath, G};
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_soi_exit_time() {
+        // ellipse from periapsis at 7000 km, crossing 10000 km on the way out
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(7000., 0., 0.),
+            DVec3::new(0., 8.5, 0.),
+            398600. / G,
+            DVec3::Z,
+        );
+        let t_exit = soi_exit_time(&test_oc, 10000., 0., test_oc.period).unwrap();
+        assert_f!(test_oc.pos_at_t(t_exit).length(), 10000.);
+        assert!(t_exit < test_oc.period / 2.);
+
+        // never leaves a sphere beyond apoapsis
+        assert!(soi_exit_time(&test_oc, test_oc.apoapsis_radius() * 1.01, 0., test_oc.period).is_none());
+        // already outside
+        assert_eq!(soi_exit_time(&test_oc, 5000., 1., test_oc.period), Some(1.));
+        // starting on the boundary on the way back in
+        let t_return = test_oc.period - t_exit;
+        assert!(soi_exit_time(&test_oc, 10000., t_return, t_return + 100.).is_none());
+    }
+
+    #[test]
+    fn test_soi_entry_time() {
+        let mass = 398600. / G;
+        let moon_oc = OrbitConic::from_initial(
+            DVec3::new(0., 384400., 0.),
+            DVec3::new(-f64::sqrt(398600. / 384400.), 0., 0.),
+            mass,
+            DVec3::Z,
+        );
+        // transfer arriving at the moon's center after three days
+        let r1 = DVec3::new(7000., 0., 0.);
+        let tof = 3. * 86400.;
+        let r2 = moon_oc.pos_at_t(tof);
+        let path = LambertPath::prograde(r1, r2, DVec3::Z);
+        let transfer_oc = lambert_solver(r1, r2, tof, path, 0, mass, DVec3::Z)[0];
+
+        let t_entry = soi_entry_time(&transfer_oc, &moon_oc, 66000., 0., 10. * 86400.).unwrap();
+        let distance = transfer_oc.pos_at_t(t_entry).distance(moon_oc.pos_at_t(t_entry));
+        assert_f!(distance, 66000.);
+        assert!(t_entry < tof);
+
+        // a sphere passed through between samples is still found
+        let t_entry = soi_entry_time(&transfer_oc, &moon_oc, 50., 0., 10. * 86400.).unwrap();
+        let distance = transfer_oc.pos_at_t(t_entry).distance(moon_oc.pos_at_t(t_entry));
+        assert_f!(distance, 50.);
+        assert!(t_entry < tof && tof - t_entry < 10. * 86400. / CROSSING_SAMPLES as f64);
+
+        // never entered when the moon is elsewhere
+        let early_moon_oc = moon_oc.with_epoch(86400.);
+        assert!(soi_entry_time(&transfer_oc, &early_moon_oc, 50., 0., tof).is_none());
+    }
+
+    #[test]
+    fn test_closest_approach() {
+        let mass = 398600. / G;
+        let moon_oc = OrbitConic::from_initial(
+            DVec3::new(0., 384400., 0.),
+            DVec3::new(-f64::sqrt(398600. / 384400.), 0., 0.),
+            mass,
+            DVec3::Z,
+        );
+        // transfer arriving 1000 km from the moon's center after three days
+        let r1 = DVec3::new(7000., 0., 0.);
+        let tof = 3. * 86400.;
+        let r2 = moon_oc.pos_at_t(tof) + DVec3::new(0., 0., 1000.);
+        let path = LambertPath::prograde(r1, r2, DVec3::Z);
+        let transfer_oc = lambert_solver(r1, r2, tof, path, 0, mass, DVec3::Z)[0];
+
+        let approach = closest_approach(&transfer_oc, &moon_oc, 0., 10. * 86400.);
+        assert!(approach.distance <= 1000.);
+        assert!((approach.t - tof).abs() < 10. * 86400. / CROSSING_SAMPLES as f64);
+        // range rate is zero at the closest approach
+        let (pos, vel) = transfer_oc.state_at(approach.t);
+        let (moon_pos, moon_vel) = moon_oc.state_at(approach.t);
+        assert_f!(approach.distance, pos.distance(moon_pos));
+        assert_f!(approach.relative_velocity.length(), (vel - moon_vel).length());
+        assert!((pos - moon_pos).normalize().dot(approach.relative_velocity.normalize()).abs() < 1e-6);
+
+        // window ending before the encounter is closest at its end
+        let approach = closest_approach(&transfer_oc, &moon_oc, 0., 86400.);
+        assert_eq!(approach.t, 86400.);
+    }
+}
@@ -0,0 +1,223 @@
+use bevy::math::DVec3;
+use thiserror::Error;
+
+// julian date of J2000 (2000-01-01 12:00)
+const J2000_JULIAN_DATE: f64 = 2451545.;
+const SECONDS_PER_DAY: f64 = 86400.;
+
+// state of a body at a time within an ephemeris table
+#[derive(Clone, Copy, Debug)]
+pub struct EphemerisRow {
+    pub t: f64,
+    pub position: DVec3,
+    pub velocity: DVec3,
+}
+
+// table of states of a body relative to its parent, ordered by time, interpolated with cubic
+// hermite splines through the positions and velocities of neighbouring rows
+#[derive(Clone, Debug, Default)]
+pub struct Ephemeris {
+    pub rows: Vec<EphemerisRow>,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum EphemerisError {
+    #[error("Could not parse ephemeris line {0}")]
+    Line(usize),
+    #[error("Ephemeris times are not increasing at line {0}")]
+    Order(usize),
+    #[error("Ephemeris has fewer than two rows")]
+    TooShort,
+}
+
+impl Ephemeris {
+
+    // parse a table of rows of time, position and velocity, separated by commas or whitespace,
+    // with times in seconds since J2000. lines starting with # are comments. a jpl horizons
+    // vector table in csv format is read from between its $$SOE and $$EOE markers instead,
+    // with times from its julian dates
+    pub fn parse(
+        text: &str,
+    ) -> Result<Ephemeris, EphemerisError> {
+        let is_horizons = text.lines().any(|line| line.trim() == "$$SOE");
+        let mut in_table = !is_horizons;
+        let mut rows: Vec<EphemerisRow> = Vec::new();
+        for (line_idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if is_horizons && (line == "$$SOE" || line == "$$EOE") {
+                in_table = line == "$$SOE";
+                continue;
+            }
+            if !in_table || line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|field| !field.is_empty()).collect();
+            // horizons rows are julian date, calendar date, position and velocity
+            let row = if is_horizons {
+                let julian_date: f64 = fields.first().and_then(|field| field.parse().ok()).ok_or(EphemerisError::Line(line_idx + 1))?;
+                let values = parse_values(&fields[fields.len().saturating_sub(6)..]).ok_or(EphemerisError::Line(line_idx + 1))?;
+                ((julian_date - J2000_JULIAN_DATE) * SECONDS_PER_DAY, values)
+            } else {
+                let values = parse_values(&fields).filter(|values| values.len() == 7).ok_or(EphemerisError::Line(line_idx + 1))?;
+                (values[0], values[1..].to_vec())
+            };
+            let (t, values) = row;
+            if values.len() != 6 {
+                return Err(EphemerisError::Line(line_idx + 1));
+            }
+            if rows.last().is_some_and(|last| last.t >= t) {
+                return Err(EphemerisError::Order(line_idx + 1));
+            }
+            rows.push(EphemerisRow {
+                t,
+                position: DVec3::new(values[0], values[1], values[2]),
+                velocity: DVec3::new(values[3], values[4], values[5]),
+            });
+        }
+        if rows.len() < 2 {
+            return Err(EphemerisError::TooShort);
+        }
+        Ok(Ephemeris { rows })
+    }
+
+    // ephemeris with times shifted by offset and positions and velocities scaled
+    pub fn transformed(
+        mut self,
+        time_offset: f64,
+        scale: f64,
+    ) -> Self {
+        for row in self.rows.iter_mut() {
+            row.t += time_offset;
+            row.position *= scale;
+            row.velocity *= scale;
+        }
+        self
+    }
+
+    pub fn start_time(
+        &self,
+    ) -> f64 {
+        self.rows[0].t
+    }
+
+    pub fn end_time(
+        &self,
+    ) -> f64 {
+        self.rows[self.rows.len() - 1].t
+    }
+
+    // interpolated position and velocity at time t within the table, none outside
+    pub fn state_at(
+        &self,
+        t: f64,
+    ) -> Option<(DVec3, DVec3)> {
+        if t < self.start_time() || t > self.end_time() {
+            return None;
+        }
+        let idx = self.rows.partition_point(|row| row.t <= t).clamp(1, self.rows.len() - 1);
+        let (row0, row1) = (&self.rows[idx - 1], &self.rows[idx]);
+        let h = row1.t - row0.t;
+        let s = (t - row0.t) / h;
+        let (s2, s3) = (s * s, s * s * s);
+        let position = (2. * s3 - 3. * s2 + 1.) * row0.position
+            + (s3 - 2. * s2 + s) * h * row0.velocity
+            + (-2. * s3 + 3. * s2) * row1.position
+            + (s3 - s2) * h * row1.velocity;
+        let velocity = ((6. * s2 - 6. * s) * row0.position
+            + (3. * s2 - 4. * s + 1.) * h * row0.velocity
+            + (-6. * s2 + 6. * s) * row1.position
+            + (3. * s2 - 2. * s) * h * row1.velocity) / h;
+        Some((position, velocity))
+    }
+
+}
+
+fn parse_values(
+    fields: &[&str],
+) -> Option<Vec<f64>> {
+    fields.iter().map(|field| field.parse().ok()).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{OrbitConic, G};
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    // moon-like orbit sampled every 6 hours
+    fn sampled_conic() -> (OrbitConic, String) {
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(384400., 0., 0.),
+            DVec3::new(0., 1., 0.1),
+            398600. / G,
+            DVec3::Z,
+        );
+        let mut text = String::from("# t x y z vx vy vz\n");
+        for n in 0..=40 {
+            let t = n as f64 * 21600.;
+            let (pos, vel) = test_oc.state_at(t);
+            text += &format!("{} {} {} {} {} {} {}\n", t, pos.x, pos.y, pos.z, vel.x, vel.y, vel.z);
+        }
+        (test_oc, text)
+    }
+
+    #[test]
+    fn test_ephemeris_interpolation() {
+        let (test_oc, text) = sampled_conic();
+        let ephemeris = Ephemeris::parse(&text).unwrap();
+        assert_eq!(ephemeris.rows.len(), 41);
+
+        // between rows
+        for t in [1000., 30000., 450000., 863999.] {
+            let (pos, vel) = ephemeris.state_at(t).unwrap();
+            let (oc_pos, oc_vel) = test_oc.state_at(t);
+            assert!(pos.distance(oc_pos) < 1e-6 * oc_pos.length());
+            assert!(vel.distance(oc_vel) < 1e-5 * oc_vel.length());
+        }
+        // exact on rows
+        let (pos, _) = ephemeris.state_at(21600.).unwrap();
+        assert_f!(pos.x, test_oc.pos_at_t(21600.).x);
+        // none outside the table
+        assert!(ephemeris.state_at(-1.).is_none());
+        assert!(ephemeris.state_at(864001.).is_none());
+
+        let shifted = ephemeris.transformed(-100., 0.001);
+        assert_eq!(shifted.start_time(), -100.);
+        assert_f!(shifted.state_at(21500.).unwrap().0.x, pos.x * 0.001);
+    }
+
+    #[test]
+    fn test_parse_horizons() {
+        let text = "*******************************************************************************
+Ephemeris / API_USER
+JDTDB, Calendar Date (TDB), X, Y, Z, VX, VY, VZ,
+*******************************************************************************
+$$SOE
+2451545.000000000, A.D. 2000-Jan-01 12:00:00.0000,  3.844000000000000E+05,  0.000000000000000E+00,  0.000000000000000E+00,  0.000000000000000E+00,  1.000000000000000E+00,  0.000000000000000E+00,
+2451545.500000000, A.D. 2000-Jan-02 00:00:00.0000,  3.840000000000000E+05,  4.320000000000000E+04,  0.000000000000000E+00, -1.000000000000000E-01,  1.000000000000000E+00,  0.000000000000000E+00,
+$$EOE
+*******************************************************************************";
+        let ephemeris = Ephemeris::parse(text).unwrap();
+        assert_eq!(ephemeris.rows.len(), 2);
+        assert_eq!(ephemeris.start_time(), 0.);
+        assert_eq!(ephemeris.end_time(), 43200.);
+        assert_eq!(ephemeris.rows[1].position, DVec3::new(384000., 43200., 0.));
+        assert_eq!(ephemeris.rows[1].velocity, DVec3::new(-0.1, 1., 0.));
+
+        // rows out of order
+        let text = "0 1 0 0 0 1 0\n10 1 1 0 0 1 0\n5 1 2 0 0 1 0\n";
+        assert!(matches!(Ephemeris::parse(text), Err(EphemerisError::Order(3))));
+        assert!(matches!(Ephemeris::parse("0 1 0 0 0 1\n"), Err(EphemerisError::Line(1))));
+    }
+}
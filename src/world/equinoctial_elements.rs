@@ -0,0 +1,165 @@
+use bevy::math::DVec3;
+use std::f64::consts::PI;
+
+use super::{reference_basis, OrbitConic, G};
+
+// modified equinoctial elements of an orbit conic, non-singular for circular and
+// equatorial orbits (only retrograde equatorial orbits, i = 180°, are singular)
+// as defined by walker, ireland and owens (1985)
+#[derive(Clone, Copy, Debug)]
+pub struct EquinoctialElements {
+    pub p: f64, // semi-latus rectum
+    pub f: f64, // e * cos(Ω + ω)
+    pub g: f64, // e * sin(Ω + ω)
+    pub h: f64, // tan(i / 2) * cos(Ω)
+    pub k: f64, // tan(i / 2) * sin(Ω)
+    pub l: f64, // true longitude, Ω + ω + ν
+}
+
+impl OrbitConic {
+
+    // calculate equinoctial elements of the initial state
+    pub fn to_equinoctial(
+        &self,
+    ) -> EquinoctialElements {
+        let mu = G * self.body_mass;
+        let tan_half_i = f64::tan(self.i / 2.);
+        let lon_periapsis = self.longitude_of_periapsis();
+        EquinoctialElements {
+            p: self.h.powi(2) / mu,
+            f: self.e * lon_periapsis.cos(),
+            g: self.e * lon_periapsis.sin(),
+            h: tan_half_i * self.big_omega.cos(),
+            k: tan_half_i * self.big_omega.sin(),
+            l: self.true_longitude(self.initial_nu),
+        }
+    }
+
+    // initializes conic from equinoctial elements, relative to the body's plane-of-reference
+    pub fn from_equinoctial(
+        elements: EquinoctialElements,
+        body_mass: f64,
+        body_plane_k: DVec3,
+    ) -> Self {
+        let (position, velocity) = elements.state_vector(G * body_mass, body_plane_k);
+        OrbitConic::from_initial(position, velocity, body_mass, body_plane_k)
+    }
+
+}
+
+impl EquinoctialElements {
+
+    // eccentricity
+    pub fn e(
+        &self,
+    ) -> f64 {
+        f64::sqrt(self.f.powi(2) + self.g.powi(2))
+    }
+
+    // inclination
+    pub fn i(
+        &self,
+    ) -> f64 {
+        2. * f64::atan(f64::sqrt(self.h.powi(2) + self.k.powi(2)))
+    }
+
+    // right ascension of ascending node, 0. for equatorial orbits
+    pub fn big_omega(
+        &self,
+    ) -> f64 {
+        f64::atan2(self.k, self.h).rem_euclid(2. * PI)
+    }
+
+    // longitude of periapsis, 0. for circular orbits
+    pub fn longitude_of_periapsis(
+        &self,
+    ) -> f64 {
+        f64::atan2(self.g, self.f).rem_euclid(2. * PI)
+    }
+
+    // calculate position and velocity directly from the equinoctial elements,
+    // without passing through the singular classical angles
+    pub fn state_vector(
+        &self,
+        mu: f64,
+        body_plane_k: DVec3,
+    ) -> (DVec3, DVec3) {
+        let (i_hat, j_hat) = reference_basis(body_plane_k);
+        let (sin_l, cos_l) = self.l.sin_cos();
+        let alpha2 = self.h.powi(2) - self.k.powi(2);
+        let s2 = 1. + self.h.powi(2) + self.k.powi(2);
+        let hk2 = 2. * self.h * self.k;
+        let w = 1. + self.f * cos_l + self.g * sin_l;
+        let r = self.p / w;
+
+        let position = r / s2 * (
+            (cos_l + alpha2 * cos_l + hk2 * sin_l) * i_hat
+            + (sin_l - alpha2 * sin_l + hk2 * cos_l) * j_hat
+            + 2. * (self.h * sin_l - self.k * cos_l) * body_plane_k);
+        let velocity = -1. / s2 * f64::sqrt(mu / self.p) * (
+            (sin_l + alpha2 * sin_l - hk2 * cos_l + self.g - hk2 * self.f + alpha2 * self.g) * i_hat
+            + (-cos_l + alpha2 * cos_l + hk2 * sin_l - self.f + hk2 * self.g + alpha2 * self.f) * j_hat
+            - 2. * (self.h * cos_l + self.k * sin_l + self.f * self.h + self.g * self.k) * body_plane_k);
+        (position, velocity)
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_equinoctial_round_trip() {
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(1000., 5000., 7000.),
+            DVec3::new(3., 4., 5.),
+            398600. / G,
+            DVec3::Z,
+        );
+        let elements = test_oc.to_equinoctial();
+        assert_f!(elements.e(), test_oc.e);
+        assert_f!(elements.i(), test_oc.i);
+        assert_f!(elements.big_omega(), test_oc.big_omega);
+
+        let (pos, vel) = elements.state_vector(398600., DVec3::Z);
+        assert_f!(pos.x, 1000.);
+        assert_f!(pos.y, 5000.);
+        assert_f!(pos.z, 7000.);
+        assert_f!(vel.x, 3.);
+        assert_f!(vel.y, 4.);
+        assert_f!(vel.z, 5.);
+    }
+
+    #[test]
+    fn test_equinoctial_circular_equatorial() {
+        // circular orbit in the y plane, as in the demo config
+        let elements = EquinoctialElements {
+            p: 1000.,
+            f: 0.,
+            g: 0.,
+            h: 0.,
+            k: 0.,
+            l: PI / 2.,
+        };
+        let test_oc = OrbitConic::from_equinoctial(elements, 398600. / G, DVec3::Y);
+        assert_f!(test_oc.initial_r.z + 0.1, -1000. + 0.1);
+        assert_f!(test_oc.e + 0.1, 0. + 0.1);
+        assert_f!(test_oc.i + 0.1, 0. + 0.1);
+        assert_f!(test_oc.true_longitude(test_oc.initial_nu), PI / 2.);
+
+        let round_trip = test_oc.to_equinoctial();
+        assert_f!(round_trip.p, 1000.);
+        assert_f!(round_trip.l, PI / 2.);
+    }
+}
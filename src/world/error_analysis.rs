@@ -0,0 +1,264 @@
+use bevy::math::DVec3;
+use std::fmt::Write;
+
+use crate::loading::SettingsConfigAsset;
+use super::{hierarchy_from_settings, nbody_from_settings, plan_transfer_from_settings, satellite_initial_states, BodyHierarchy, Integrator, ManeuverNode, OrbitConic};
+
+// difference between the patched conics and the n-body integration of a satellite, both
+// measured relative to the body the satellite orbits under patched conics
+#[derive(Clone, Copy, Debug)]
+pub struct ErrorSample {
+    pub t: f64,
+    pub satellite: usize, // index of the satellite within the settings
+    pub body: usize, // index of the orbited body within the body hierarchy
+    pub segment: usize, // number of soi transitions before this sample
+    pub position_error: f64,
+    pub velocity_error: f64,
+}
+
+// difference at the time of a soi transition of a satellite, before changing frame
+#[derive(Clone, Copy, Debug)]
+pub struct TransitionError {
+    pub t: f64,
+    pub satellite: usize,
+    pub from_body: usize,
+    pub to_body: usize,
+    pub position_error: f64,
+    pub velocity_error: f64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ErrorAnalysis {
+    pub samples: Vec<ErrorSample>,
+    pub transitions: Vec<TransitionError>,
+}
+
+// satellite propagated on patched conics
+struct PatchedSatellite {
+    conic: OrbitConic,
+    body: usize,
+    maneuver: Option<ManeuverNode>,
+    queue: Vec<ManeuverNode>, // burns after the maneuver, in time order
+    segment: usize,
+}
+
+// next event of a satellite, processed in time order across every satellite
+enum Event {
+    Transition(usize),
+    Maneuver(ManeuverNode),
+}
+
+// runs the scenario of the settings for duration on patched conics, as the demo predicts them,
+// and with the n-body integrator in steps no longer than max_step, sampling the divergence
+// every sample_interval and at each soi transition. both include the J2 of oblate bodies, as
+// secular drift on the conics and as full acceleration in the integration
+pub fn run_error_analysis(
+    settings: &SettingsConfigAsset,
+    integrator: Integrator,
+    duration: f64,
+    sample_interval: f64,
+    max_step: f64,
+) -> ErrorAnalysis {
+    let hierarchy = hierarchy_from_settings(settings);
+    let mut system = nbody_from_settings(&hierarchy, settings);
+    let sat_offset = hierarchy.bodies.len();
+    let sat_states = satellite_initial_states(&hierarchy, settings);
+    let mut sats: Vec<PatchedSatellite> = settings.satellites.iter().zip(sat_states).map(|(sat, (pos, vel))| {
+        let conic = hierarchy.conic_about(sat.parent, 0., pos, vel);
+        let maneuver = sat.maneuver.as_ref().map(|maneuver| ManeuverNode {
+            t: maneuver.t,
+            prograde: maneuver.prograde,
+            normal: maneuver.normal,
+            radial: maneuver.radial,
+        });
+        // a planned transfer replaces the maneuver, as in the demo
+        let plan = sat.transfer.as_ref()
+            .and_then(|transfer| plan_transfer_from_settings(&hierarchy, sat.parent, &conic, transfer));
+        let (maneuver, queue) = match plan {
+            Some(plan) => (plan.burns.first().copied(), plan.burns.iter().skip(1).copied().collect()),
+            None => (maneuver, Vec::new()),
+        };
+        PatchedSatellite { conic, body: sat.parent, maneuver, queue, segment: 0 }
+    }).collect();
+
+    // position and velocity errors of satellite idx at time t
+    let errors = |hierarchy: &BodyHierarchy, positions: &[DVec3], velocities: &[DVec3], sat: &PatchedSatellite, idx: usize, t: f64| {
+        let (pos, vel) = hierarchy.j2_precessed(sat.body, &sat.conic, t).state_at(t);
+        let nbody_pos = positions[sat_offset + idx] - positions[sat.body];
+        let nbody_vel = velocities[sat_offset + idx] - velocities[sat.body];
+        (pos.distance(nbody_pos), vel.distance(nbody_vel))
+    };
+
+    let mut analysis = ErrorAnalysis::default();
+    let mut t = 0.;
+    loop {
+        for (idx, sat) in sats.iter().enumerate() {
+            let (position_error, velocity_error) = errors(&hierarchy, &system.positions, &system.velocities, sat, idx, t);
+            analysis.samples.push(ErrorSample { t, satellite: idx, body: sat.body, segment: sat.segment, position_error, velocity_error });
+        }
+        if t >= duration {
+            break;
+        }
+        let t_sample = (t + sample_interval).min(duration);
+
+        // transitions and burns before the next sample, earliest first
+        loop {
+            let mut next_event: Option<(f64, usize, Event)> = None;
+            for (idx, sat) in sats.iter().enumerate() {
+                let mean_conic = hierarchy.j2_precessed(sat.body, &sat.conic, t);
+                let t_end = sat.maneuver.map_or(t_sample, |node| node.t.clamp(t, t_sample));
+                let event = match hierarchy.next_soi_transition(sat.body, &mean_conic, t, t_end) {
+                    Some((t_change, new_body)) => Some((t_change, idx, Event::Transition(new_body))),
+                    None => sat.maneuver.filter(|node| node.t <= t_sample).map(|node| (node.t.max(t), idx, Event::Maneuver(node))),
+                };
+                if let Some(event) = event.filter(|event| !next_event.as_ref().is_some_and(|next| event.0 >= next.0)) {
+                    next_event = Some(event);
+                }
+            }
+            let Some((t_event, idx, event)) = next_event else {
+                break;
+            };
+            system.advance(t_event - t, max_step, integrator);
+            t = t_event;
+
+            let sat = &mut sats[idx];
+            match event {
+                Event::Transition(new_body) => {
+                    let (position_error, velocity_error) = errors(&hierarchy, &system.positions, &system.velocities, sat, idx, t);
+                    analysis.transitions.push(TransitionError {
+                        t, satellite: idx, from_body: sat.body, to_body: new_body, position_error, velocity_error });
+                    let (pos, vel) = hierarchy.j2_precessed(sat.body, &sat.conic, t).state_at(t);
+                    let (pos, vel) = hierarchy.change_frame(sat.body, new_body, t, pos, vel);
+                    sat.conic = hierarchy.conic_about(new_body, t, pos, vel);
+                    sat.body = new_body;
+                    sat.segment += 1;
+                    // burns are planned about the body left behind, the demo drops them
+                    sat.maneuver = None;
+                    sat.queue.clear();
+                }
+                Event::Maneuver(node) => {
+                    // same burn for both, in the frame of the orbited body
+                    let pos = system.positions[sat_offset + idx] - system.positions[sat.body];
+                    let vel = system.velocities[sat_offset + idx] - system.velocities[sat.body];
+                    system.velocities[sat_offset + idx] += node.delta_v_at(pos, vel);
+                    let node = ManeuverNode { t, ..node };
+                    sat.conic = node.apply(&hierarchy.j2_precessed(sat.body, &sat.conic, t));
+                    sat.maneuver = (!sat.queue.is_empty()).then(|| sat.queue.remove(0));
+                }
+            }
+        }
+
+        system.advance(t_sample - t, max_step, integrator);
+        t = t_sample;
+    }
+    analysis
+}
+
+impl ErrorAnalysis {
+
+    // samples and transitions as csv, one row each, with the kind of row in the first column
+    pub fn to_csv(
+        &self,
+        hierarchy: &BodyHierarchy,
+    ) -> String {
+        let mut csv = String::from("kind,t,satellite,body,to_body,segment,position_error,velocity_error\n");
+        for sample in &self.samples {
+            writeln!(csv, "sample,{},{},{},,{},{},{}", sample.t, sample.satellite,
+                hierarchy.bodies[sample.body].name, sample.segment,
+                sample.position_error, sample.velocity_error).unwrap();
+        }
+        for transition in &self.transitions {
+            writeln!(csv, "transition,{},{},{},{},,{},{}", transition.t, transition.satellite,
+                hierarchy.bodies[transition.from_body].name, hierarchy.bodies[transition.to_body].name,
+                transition.position_error, transition.velocity_error).unwrap();
+        }
+        csv
+    }
+
+    // samples and transitions as aligned tables
+    pub fn to_table(
+        &self,
+        hierarchy: &BodyHierarchy,
+    ) -> String {
+        let mut table = format!("{:>10} {:>4} {:>12} {:>4} {:>14} {:>14}\n",
+            "t", "sat", "body", "seg", "pos error", "vel error");
+        for sample in &self.samples {
+            writeln!(table, "{:>10.3} {:>4} {:>12} {:>4} {:>14.6e} {:>14.6e}", sample.t, sample.satellite,
+                hierarchy.bodies[sample.body].name, sample.segment,
+                sample.position_error, sample.velocity_error).unwrap();
+        }
+        writeln!(table, "\nsoi transitions:").unwrap();
+        writeln!(table, "{:>10} {:>4} {:>12} {:>12} {:>14} {:>14}",
+            "t", "sat", "from", "to", "pos error", "vel error").unwrap();
+        for transition in &self.transitions {
+            writeln!(table, "{:>10.3} {:>4} {:>12} {:>12} {:>14.6e} {:>14.6e}", transition.t, transition.satellite,
+                hierarchy.bodies[transition.from_body].name, hierarchy.bodies[transition.to_body].name,
+                transition.position_error, transition.velocity_error).unwrap();
+        }
+        table
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loading::TransferConfig;
+    use crate::world::G;
+    use bevy::math::Vec3;
+
+    // the demo's settings, a planet with a moon and moonlet and a satellite passing between them
+    fn demo_settings() -> SettingsConfigAsset {
+        ron::de::from_str(include_str!("../../assets/config/settings.config")).unwrap()
+    }
+
+    #[test]
+    fn test_error_analysis() {
+        let settings = demo_settings();
+        let analysis = run_error_analysis(&settings, Integrator::Rk4, 20., 0.5, 1. / 240.);
+        assert_eq!(analysis.samples.len(), 41 * settings.satellites.len());
+
+        // identical at the start, then diverging
+        assert!(analysis.samples[0].position_error < 1e-12);
+        assert!(analysis.samples[0].velocity_error < 1e-12);
+        assert!(analysis.samples.last().unwrap().position_error > 0.);
+
+        // each transition moves the following samples into the next segment
+        let last = analysis.samples.last().unwrap();
+        assert_eq!(last.segment, analysis.transitions.len());
+        for transition in &analysis.transitions {
+            assert!(transition.t > 0. && transition.t < 20.);
+            assert_ne!(transition.from_body, transition.to_body);
+        }
+    }
+
+    #[test]
+    fn test_error_analysis_transfer() {
+        // satellite on a circular orbit of the planet coplanar with a light moon, which it
+        // never reaches on its own
+        let mut settings = demo_settings();
+        settings.bodies.truncate(2);
+        settings.bodies[1].mass /= 100.;
+        settings.bodies[1].radius = 0.05;
+        let mu = G * settings.bodies[0].mass;
+        settings.satellites[0].initial_vel = Vec3::new((mu / 1.65).sqrt() as f32, 0., 0.);
+        let analysis = run_error_analysis(&settings, Integrator::Rk4, 12., 0.5, 1. / 240.);
+        assert!(analysis.transitions.is_empty());
+
+        // a transfer to the moon replaces the maneuver and leaves the planet's soi for the
+        // moon's, with the integration arriving well within the moon's soi too
+        settings.satellites[0].transfer = Some(TransferConfig {
+            after: 0.,
+            target_radius: None,
+            target_body: Some(1),
+            periapsis_offset: None,
+            bi_elliptic_radius: None,
+        });
+        let analysis = run_error_analysis(&settings, Integrator::Rk4, 12., 0.5, 1. / 240.);
+        let transition = analysis.transitions.first().unwrap();
+        assert_eq!((transition.from_body, transition.to_body), (0, 1));
+        let moon_soi = hierarchy_from_settings(&settings).bodies[1].soi_radius;
+        assert!(transition.position_error < moon_soi / 2.);
+    }
+}
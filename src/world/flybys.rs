@@ -0,0 +1,129 @@
+use bevy::math::DVec3;
+
+use super::{BodyHierarchy, OrbitConic};
+
+// gravity assist of an object passing through the soi of a body on an open conic. vectors are
+// relative to the body unless noted, angles in radians
+#[derive(Clone, Copy, Debug)]
+pub struct FlybyReport {
+    pub body: usize, // index of the body flown by within the body hierarchy
+    pub t_entry: f64, // simulation time of entering the body's soi
+    pub t_periapsis: f64,
+    pub t_exit: f64, // simulation time of leaving the body's soi
+    pub periapsis_altitude: f64, // negative when passing below the surface
+    pub v_inf_in: DVec3, // hyperbolic excess velocity along the incoming asymptote
+    pub v_inf_out: DVec3, // hyperbolic excess velocity along the outgoing asymptote
+    pub turning_angle: f64, // between the incoming and outgoing asymptotes
+    pub b_dot_t: f64, // b-plane components of the miss vector, t within the body's
+    pub b_dot_r: f64, // plane-of-reference and r completing the frame with the asymptote
+    pub delta_v: DVec3, // change of velocity relative to the parent body from entry to exit
+}
+
+impl BodyHierarchy {
+
+    // flyby of body idx by an object on conic about it from t_entry, none for closed conics or
+    // the root body, which has no parent to gain velocity relative to
+    pub fn flyby_report(
+        &self,
+        idx: usize,
+        conic: &OrbitConic,
+        t_entry: f64,
+    ) -> Option<FlybyReport> {
+        let body = &self.bodies[idx];
+        let parent = body.parent?;
+        let v_inf = conic.v_inf()?;
+        let t_exit = conic.outbound_time_at_radius(body.soi_radius)?.max(t_entry);
+        let t_periapsis = (t_entry + conic.time_to_periapsis(t_entry).unwrap_or(0.)).min(t_exit);
+
+        // asymptote directions in the orbital plane, at true anomalies of ±acos(-1/e)
+        let (e_hat, q_hat, h_hat) = conic.perifocal_basis();
+        let (cos_nu, sin_nu) = (-1. / conic.e, f64::sqrt(1. - 1. / conic.e.powi(2)));
+        let s_hat = -cos_nu * e_hat + sin_nu * q_hat;
+        let v_inf_in = v_inf * s_hat;
+        let v_inf_out = v_inf * (cos_nu * e_hat + sin_nu * q_hat);
+
+        // miss vector from the body to the incoming asymptote, towards periapsis, in the
+        // b-plane normal to the incoming asymptote
+        let b_vec = conic.h / v_inf * s_hat.cross(h_hat);
+        let t_hat = s_hat.cross(body.body_plane_k).try_normalize().unwrap_or(e_hat);
+        let r_hat = s_hat.cross(t_hat);
+
+        // velocities relative to the parent at soi entry and exit
+        let parent_vel = |t: f64| {
+            let (pos, vel) = conic.state_at(t);
+            self.change_frame(idx, parent, t, pos, vel).1
+        };
+
+        Some(FlybyReport {
+            body: idx,
+            t_entry,
+            t_periapsis,
+            t_exit,
+            periapsis_altitude: conic.periapsis_altitude(body.radius),
+            v_inf_in,
+            v_inf_out,
+            turning_angle: conic.turning_angle()?,
+            b_dot_t: b_vec.dot(t_hat),
+            b_dot_r: b_vec.dot(r_hat),
+            delta_v: parent_vel(t_exit) - parent_vel(t_entry),
+        })
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::test_hierarchy;
+    use std::f64::consts::PI;
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_flyby_report() {
+        let hierarchy = test_hierarchy();
+        let soi_radius = hierarchy.bodies[2].soi_radius;
+
+        // entering the moon's soi in its orbital plane, turned through more than 90°
+        let speed = 4e4;
+        let offset = 200.;
+        let entry_pos = DVec3::new(offset, -f64::sqrt(soi_radius.powi(2) - offset.powi(2)), 0.);
+        let conic = hierarchy.conic_about(2, 5., entry_pos, DVec3::new(0., speed, 0.));
+        let report = hierarchy.flyby_report(2, &conic, 5.).unwrap();
+        assert!(report.t_entry < report.t_periapsis && report.t_periapsis < report.t_exit);
+        assert_f!(conic.pos_at_t(report.t_exit).length(), soi_radius);
+        assert_f!(report.periapsis_altitude, conic.periapsis_radius() - 10.);
+        assert!(report.periapsis_altitude > 0. && report.turning_angle > PI / 2.);
+
+        // symmetric asymptotes turned by the turning angle, the incoming one close to the
+        // velocity at entry
+        assert_f!(report.v_inf_in.length(), report.v_inf_out.length());
+        assert_f!(report.v_inf_in.angle_between(report.v_inf_out), report.turning_angle);
+        assert!(report.v_inf_in.normalize().dot(DVec3::Y) > 0.98);
+
+        // miss vector in the moon's plane, of the impact parameter h / v_inf
+        assert_f!(report.b_dot_t.abs(), conic.h / report.v_inf_in.length());
+        assert!(report.b_dot_r.abs() < 1e-9);
+
+        // the change of velocity about the planet is the turn of the velocity about the moon
+        // between the soi crossings, plus the moon's own small change meanwhile
+        let turn = conic.vel_at_t(report.t_exit) - conic.vel_at_t(report.t_entry);
+        let moon_turn = hierarchy.body_state_at(2, report.t_exit).1 - hierarchy.body_state_at(2, report.t_entry).1;
+        assert_f!(report.delta_v.length(), (turn + moon_turn).length());
+        assert!(report.delta_v.dot(report.v_inf_out - report.v_inf_in) > 0.);
+
+        // closed conics and the root body have no flyby
+        let closed = hierarchy.conic_about(2, 0., DVec3::new(100., 0., 0.), DVec3::new(0., 1., 0.));
+        assert!(hierarchy.flyby_report(2, &closed, 0.).is_none());
+        let about_root = hierarchy.conic_about(0, 0., DVec3::new(1e4, 0., 0.), DVec3::new(0., 1e4, 0.));
+        assert!(hierarchy.flyby_report(0, &about_root, 0.).is_none());
+    }
+}
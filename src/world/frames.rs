@@ -0,0 +1,272 @@
+use bevy::math::{DMat3, DVec3};
+use std::f64::consts::PI;
+
+use super::{reference_basis, BodyHierarchy, OrbitConic};
+
+// reference frame defined within a body's simulation frame, the frame conics are expressed in,
+// centered on the body with the simulation's axes. positions and velocities convert through it
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    pub origin: DVec3, // position of the frame's origin
+    pub origin_vel: DVec3, // velocity of the frame's origin
+    pub axes: DMat3, // columns are the frame's x, y and z axes
+    pub angular_velocity: DVec3, // rate of rotation of the axes
+}
+
+impl Frame {
+
+    // body-centered, non-rotating frame with axes along the reference basis of the
+    // body's plane-of-reference, z along body_plane_k
+    pub fn inertial(
+        body_plane_k: DVec3,
+    ) -> Self {
+        let (i_hat, j_hat) = reference_basis(body_plane_k);
+        Frame {
+            origin: DVec3::ZERO,
+            origin_vel: DVec3::ZERO,
+            axes: DMat3::from_cols(i_hat, j_hat, body_plane_k),
+            angular_velocity: DVec3::ZERO,
+        }
+    }
+
+    // body-centered frame of a conic's orbital plane, x towards periapsis and z along
+    // angular momentum
+    pub fn perifocal(
+        conic: &OrbitConic,
+    ) -> Self {
+        let (p_hat, q_hat, w_hat) = conic.perifocal_basis();
+        Frame {
+            origin: DVec3::ZERO,
+            origin_vel: DVec3::ZERO,
+            axes: DMat3::from_cols(p_hat, q_hat, w_hat),
+            angular_velocity: DVec3::ZERO,
+        }
+    }
+
+    // body-centered frame rotating with the body about body_plane_k at simulation time t,
+    // aligned with the inertial frame at time 0. not rotating for an infinite period
+    pub fn body_fixed(
+        body_plane_k: DVec3,
+        rotation_period: f64,
+        t: f64,
+    ) -> Self {
+        let rate = 2. * PI / rotation_period;
+        let (i_hat, j_hat) = reference_basis(body_plane_k);
+        let (sin_a, cos_a) = (rate * t).sin_cos();
+        Frame {
+            origin: DVec3::ZERO,
+            origin_vel: DVec3::ZERO,
+            axes: DMat3::from_cols(
+                i_hat * cos_a + j_hat * sin_a,
+                -i_hat * sin_a + j_hat * cos_a,
+                body_plane_k),
+            angular_velocity: body_plane_k * rate,
+        }
+    }
+
+    // frame rotating with a secondary body of given mass on conic about the body at simulation
+    // time t, centered on their barycenter with x towards the secondary and z along its angular
+    // momentum, so both bodies are at rest on the x axis for circular orbits
+    pub fn synodic(
+        conic: &OrbitConic,
+        secondary_mass: f64,
+        t: f64,
+    ) -> Self {
+        let (pos, vel) = conic.state_at(t);
+        let mass_ratio = secondary_mass / (conic.body_mass + secondary_mass);
+        let x_hat = pos.normalize();
+        let z_hat = pos.cross(vel).normalize();
+        Frame {
+            origin: pos * mass_ratio,
+            origin_vel: vel * mass_ratio,
+            axes: DMat3::from_cols(x_hat, z_hat.cross(x_hat), z_hat),
+            angular_velocity: pos.cross(vel) / pos.length_squared(),
+        }
+    }
+
+    // convert position and velocity from the simulation frame into this frame
+    pub fn to_frame(
+        &self,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> (DVec3, DVec3) {
+        let offset = position - self.origin;
+        let inverse = self.axes.transpose();
+        (inverse * offset,
+            inverse * (velocity - self.origin_vel - self.angular_velocity.cross(offset)))
+    }
+
+    // convert position and velocity from this frame into the simulation frame
+    pub fn from_frame(
+        &self,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> (DVec3, DVec3) {
+        let offset = self.axes * position;
+        (self.origin + offset,
+            self.origin_vel + self.axes * velocity + self.angular_velocity.cross(offset))
+    }
+
+    // convert position and velocity from this frame into another frame of the same body
+    pub fn convert_to(
+        &self,
+        other: &Frame,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> (DVec3, DVec3) {
+        let (position, velocity) = self.from_frame(position, velocity);
+        other.to_frame(position, velocity)
+    }
+
+}
+
+impl BodyHierarchy {
+
+    // inertial frame of body idx
+    pub fn inertial_frame(
+        &self,
+        idx: usize,
+    ) -> Frame {
+        Frame::inertial(self.bodies[idx].body_plane_k)
+    }
+
+    // frame rotating with body idx at simulation time t
+    pub fn body_fixed_frame(
+        &self,
+        idx: usize,
+        t: f64,
+    ) -> Frame {
+        let body = &self.bodies[idx];
+        Frame::body_fixed(body.body_plane_k, body.rotation_period, t)
+    }
+
+    // synodic frame of body idx and its parent, defined within the parent's simulation frame,
+    // none for the root
+    pub fn synodic_frame(
+        &self,
+        idx: usize,
+        t: f64,
+    ) -> Option<Frame> {
+        self.body_conic_at(idx, t).map(|conic| Frame::synodic(&conic, self.bodies[idx].mass, t))
+    }
+
+    // convert a state relative to body from_idx in frame from_frame into frame to_frame of
+    // body to_idx, at simulation time t
+    #[allow(clippy::too_many_arguments)]
+    pub fn convert_frame(
+        &self,
+        from_idx: usize,
+        from_frame: &Frame,
+        to_idx: usize,
+        to_frame: &Frame,
+        t: f64,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> (DVec3, DVec3) {
+        let (position, velocity) = from_frame.from_frame(position, velocity);
+        let (position, velocity) = self.change_frame(from_idx, to_idx, t, position, velocity);
+        to_frame.to_frame(position, velocity)
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{SoiModel, G};
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_perifocal_frame() {
+        // curtis example 4.7
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(-6045., -3490., 2500.),
+            DVec3::new(-3.457, 6.618, 2.533),
+            398600. / G,
+            DVec3::Z,
+        );
+        let frame = Frame::perifocal(&test_oc);
+        let (pos, vel) = frame.to_frame(test_oc.initial_r, test_oc.initial_v);
+        let r = test_oc.initial_r.length();
+        let v_scale = 398600. / test_oc.h;
+        assert_f!(pos.x, r * test_oc.initial_nu.cos());
+        assert_f!(pos.y, r * test_oc.initial_nu.sin());
+        assert_f!(pos.z + 0.1, 0. + 0.1);
+        assert_f!(vel.x, -v_scale * test_oc.initial_nu.sin());
+        assert_f!(vel.y, v_scale * (test_oc.e + test_oc.initial_nu.cos()));
+        assert_f!(vel.z + 0.1, 0. + 0.1);
+
+        // and back
+        let (pos, vel) = frame.from_frame(pos, vel);
+        assert_f!(pos.x, -6045.);
+        assert_f!(vel.z, 2.533);
+    }
+
+    #[test]
+    fn test_body_fixed_frame() {
+        // synchronous orbit in the y plane stays fixed above the surface
+        let period = 86164.;
+        let r = f64::cbrt(398600. * (period / (2. * PI)).powi(2));
+        let v = 2. * PI * r / period;
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(r, 0., 0.),
+            DVec3::new(0., 0., -v),
+            398600. / G,
+            DVec3::Y,
+        );
+        let inertial = Frame::inertial(DVec3::Y);
+        let (start_pos, _) = inertial.to_frame(test_oc.initial_r, test_oc.initial_v);
+        for t in [1000., 20000., 50000.] {
+            let (pos, vel) = test_oc.state_at(t);
+            let (pos, vel) = Frame::body_fixed(DVec3::Y, period, t).to_frame(pos, vel);
+            assert_f!(pos.x, start_pos.x);
+            assert_f!(pos.y + 0.1, start_pos.y + 0.1);
+            assert!(vel.length() < 1e-6);
+        }
+
+        // no rotation for an infinite period
+        let frame = Frame::body_fixed(DVec3::Y, f64::INFINITY, 1000.);
+        let (pos, _) = frame.convert_to(&inertial, DVec3::X, DVec3::ZERO);
+        assert_f!(pos.x, 1.);
+    }
+
+    #[test]
+    fn test_synodic_frame() {
+        let mut hierarchy = BodyHierarchy::new("Earth", 398600. / G, 6378., DVec3::Z);
+        hierarchy.bodies[0].rotation_period = 86164.;
+        let v = f64::sqrt(398600. / 384400.);
+        let moon = hierarchy.add_body("Moon", 0, 4903. / G, 1737.,
+            DVec3::new(384400., 0., 0.), DVec3::new(0., v, 0.), DVec3::Z, SoiModel::Laplace);
+        let mass_ratio = 4903. / (398600. + 4903.);
+
+        // both bodies at rest on the x axis, either side of the barycenter
+        for t in [0., 86400., 5. * 86400.] {
+            let frame = hierarchy.synodic_frame(moon, t).unwrap();
+            let (moon_pos, moon_vel) = hierarchy.bodies[moon].conic.unwrap().state_at(t);
+            let (pos, vel) = frame.to_frame(moon_pos, moon_vel);
+            assert_f!(pos.x, 384400. * (1. - mass_ratio));
+            assert!(pos.y.abs() < 1e-6 && vel.length() < 1e-9);
+            let (pos, vel) = frame.to_frame(DVec3::ZERO, DVec3::ZERO);
+            assert_f!(pos.x, -384400. * mass_ratio);
+            assert!(vel.length() < 1e-9);
+        }
+
+        // satellite about the moon seen from the earth's body-fixed frame and back
+        let body_fixed = hierarchy.body_fixed_frame(0, 1000.);
+        let moon_inertial = hierarchy.inertial_frame(moon);
+        let (pos, vel) = hierarchy.convert_frame(moon, &moon_inertial, 0, &body_fixed, 1000.,
+            DVec3::new(2000., 0., 0.), DVec3::new(0., 1.5, 0.));
+        let (pos, vel) = hierarchy.convert_frame(0, &body_fixed, moon, &moon_inertial, 1000., pos, vel);
+        assert_f!(pos.x, 2000.);
+        assert_f!(vel.y, 1.5);
+    }
+}
@@ -0,0 +1,135 @@
+use bevy::math::DVec3;
+use std::f64::consts::PI;
+
+use super::{BodyHierarchy, OrbitConic};
+
+// point of a ground track, the sub-satellite point on a body at a time, angles in radians
+#[derive(Clone, Copy, Debug)]
+pub struct GroundTrackPoint {
+    pub t: f64,
+    pub latitude: f64,
+    pub longitude: f64, // within -π to π, from the body-fixed x axis
+}
+
+impl BodyHierarchy {
+
+    // latitude and longitude on body idx at simulation time t below a position relative to it,
+    // in the body-fixed frame
+    pub fn surface_coordinates(
+        &self,
+        idx: usize,
+        t: f64,
+        position: DVec3,
+    ) -> (f64, f64) {
+        let (surface_position, _) = self.body_fixed_frame(idx, t).to_frame(position, DVec3::ZERO);
+        ((surface_position.z / surface_position.length()).clamp(-1., 1.).asin(),
+            surface_position.y.atan2(surface_position.x))
+    }
+
+    // ground track of an object on conic about body idx from t_start to t_end, sampled at
+    // steps_per_orbit points per ground_track_orbit_time, with the body's oblateness drift
+    // applied to the conic at each sample
+    pub fn ground_track(
+        &self,
+        idx: usize,
+        conic: &OrbitConic,
+        t_start: f64,
+        t_end: f64,
+        steps_per_orbit: usize,
+    ) -> Vec<GroundTrackPoint> {
+        let step = ground_track_orbit_time(conic) / steps_per_orbit as f64;
+        let steps = ((t_end - t_start) / step).ceil().max(1.) as usize;
+        (0..=steps).map(|n| {
+            let t = (t_start + n as f64 * step).min(t_end);
+            let position = self.j2_precessed(idx, conic, t).pos_at_t(t);
+            let (latitude, longitude) = self.surface_coordinates(idx, t, position);
+            GroundTrackPoint { t, latitude, longitude }
+        }).collect()
+    }
+
+}
+
+// time of one orbit of a conic over which its ground track is sampled, the period of closed
+// conics, and for open ones a revolution at the angular rate of periapsis, h / r_p²
+pub fn ground_track_orbit_time(
+    conic: &OrbitConic,
+) -> f64 {
+    if conic.e < 1. {
+        conic.period
+    } else {
+        2. * PI * conic.periapsis_radius().powi(2) / conic.h
+    }
+}
+
+// splits a ground track into runs that do not cross the ±180° longitude seam, for drawing on an
+// equirectangular map
+pub fn ground_track_segments(
+    track: &[GroundTrackPoint],
+) -> Vec<&[GroundTrackPoint]> {
+    let mut segments: Vec<&[GroundTrackPoint]> = Vec::new();
+    let mut start = 0;
+    for idx in 1..track.len() {
+        if (track[idx].longitude - track[idx - 1].longitude).abs() > PI {
+            segments.push(&track[start..idx]);
+            start = idx;
+        }
+    }
+    if start < track.len() {
+        segments.push(&track[start..]);
+    }
+    segments
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::G;
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ground_track() {
+        let mut hierarchy = BodyHierarchy::new("Earth", 398600. / G, 6378., DVec3::Z);
+        hierarchy.bodies[0].rotation_period = 86164.;
+
+        // equatorial circular orbit drifts in longitude at the orbital rate less the body's
+        let test_oc = OrbitConic::from_elements(7000., 0., 0., 0., 0., 0., 398600. / G, DVec3::Z);
+        let track = hierarchy.ground_track(0, &test_oc, 0., 1000., 64);
+        assert_eq!(track[0].t, 0.);
+        assert_eq!(track.last().unwrap().t, 1000.);
+        let rate = 2. * PI / test_oc.period - 2. * PI / 86164.;
+        assert_f!(track.last().unwrap().longitude, rate * 1000.);
+        assert!(track.iter().all(|point| point.latitude.abs() < 1e-9));
+
+        // inclined orbit reaches its inclination in latitude, crossing the seam once per
+        // revolution relative to the surface
+        let test_oc = OrbitConic::from_elements(7000., 0., 0.9, 0., 0., 0., 398600. / G, DVec3::Z);
+        let track = hierarchy.ground_track(0, &test_oc, 0., test_oc.period * 2., 256);
+        let max_latitude = track.iter().map(|point| point.latitude).fold(f64::MIN, f64::max);
+        assert!((max_latitude - 0.9).abs() < 1e-3);
+        assert_eq!(ground_track_segments(&track).len(), 3);
+        let (latitude, longitude) = hierarchy.surface_coordinates(0, 0., test_oc.pos_at_t(0.));
+        assert_eq!((track[0].latitude, track[0].longitude), (latitude, longitude));
+
+        // equatorial hyperbola through periapsis long after time 0, sampled at the angular rate
+        // of periapsis and moving eastward fastest there
+        let test_oc = OrbitConic::from_initial(DVec3::new(7000., 0., 0.), DVec3::new(0., 12., 0.), 398600. / G, DVec3::Z)
+            .with_epoch(5000.);
+        let orbit_time = ground_track_orbit_time(&test_oc);
+        assert_f!(orbit_time, 2. * PI * 7000. / 12.);
+        let track = hierarchy.ground_track(0, &test_oc, 5000. - orbit_time / 2., 5000. + orbit_time / 2., 64);
+        assert_eq!(track.len(), 65);
+        assert_f!(track[1].t - track[0].t, orbit_time / 64.);
+        assert!(track.iter().all(|point| point.latitude.abs() < 1e-9));
+        let drift = |n: usize| (track[n + 1].longitude - track[n].longitude).rem_euclid(2. * PI);
+        assert!(drift(32) > drift(0) && drift(32) > drift(63));
+    }
+}
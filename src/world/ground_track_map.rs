@@ -0,0 +1,131 @@
+use std::f64::consts::PI;
+
+use crate::GameState;
+use crate::overlay_ui::{OverylayUiControls,ViewingBody};
+use crate::world::{ground_track_orbit_time,ground_track_segments,BodyHierarchy,Crashed,Escaped,GroundTrackPoint,ManeuverNode,SatEntity,SimulationState};
+
+use bevy::prelude::*;
+use bevy::render::camera::ClearColorConfig;
+use bevy::render::view::RenderLayers;
+use bevy::window::PrimaryWindow;
+
+// size in pixels of the equirectangular map, twice as wide as tall
+const MAP_WIDTH: f32 = 480.;
+const MAP_HEIGHT: f32 = 240.;
+// distance in pixels from the top right corner of the window
+const MAP_MARGIN: f32 = 8.;
+// number of orbits of the selected satellite drawn ahead
+const GROUND_TRACK_ORBITS: f64 = 3.;
+// samples of the ground track per orbit
+const GROUND_TRACK_STEPS: usize = 128;
+// render layer of the map, drawn by its own camera over the scene
+const MAP_LAYER: u8 = 1;
+
+// This plugin draws the ground track of the selected satellite on a 2d map
+pub struct GroundTrackMapPlugin;
+impl Plugin for GroundTrackMapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_gizmo_group(GroundTrackMapGizmos, GizmoConfig {
+                render_layers: RenderLayers::layer(MAP_LAYER),
+                ..default()
+            })
+            .add_systems(OnEnter(GameState::Playing), setup_ground_track_map)
+            .add_systems(Update, update_ground_track_map.run_if(in_state(GameState::Playing)));
+    }
+}
+
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct GroundTrackMapGizmos;
+
+#[derive(Component)]
+pub struct GroundTrackMapCamera;
+
+fn setup_ground_track_map(
+    mut commands: Commands,
+) {
+    // 2d camera over the scene for the map layer only
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                order: 1,
+                clear_color: ClearColorConfig::None,
+                ..default()
+            },
+            ..default()
+        },
+        RenderLayers::layer(MAP_LAYER),
+        GroundTrackMapCamera,
+    ));
+}
+
+fn update_ground_track_map(
+    mut controls: ResMut<OverylayUiControls>,
+    simulation_state: Res<SimulationState>,
+    hierarchy: Res<BodyHierarchy>,
+    mut gizmos: Gizmos<GroundTrackMapGizmos>,
+    sat_query: Query<(&SatEntity, Option<&ManeuverNode>, Option<&Crashed>, Option<&Escaped>)>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    key: Res<ButtonInput<KeyCode>>,
+) {
+    if key.just_pressed(KeyCode::KeyM) {
+        controls.show_ground_track = !controls.show_ground_track;
+    }
+    if !controls.show_ground_track {
+        return;
+    }
+    let ViewingBody::Satellite(sat_idx) = controls.viewing_body else {
+        return;
+    };
+    let Some((sat, maneuver, crashed, escaped)) = sat_query.iter().find(|(sat, ..)| sat.idx == sat_idx) else {
+        return;
+    };
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    // map in the top right corner, with the 2d camera's origin at the window's center
+    let center = Vec2::new(
+        window.width() / 2. - MAP_MARGIN - MAP_WIDTH / 2.,
+        window.height() / 2. - MAP_MARGIN - MAP_HEIGHT / 2.);
+    let to_map = |latitude: f64, longitude: f64| center + Vec2::new(
+        (longitude / PI) as f32 * MAP_WIDTH / 2.,
+        (latitude / (PI / 2.)) as f32 * MAP_HEIGHT / 2.);
+
+    // outline and a graticule every 30°
+    gizmos.rect_2d(center, 0., Vec2::new(MAP_WIDTH, MAP_HEIGHT), Color::GRAY);
+    for n in -5..=5 {
+        let longitude = (n * 30) as f64 * PI / 180.;
+        gizmos.line_2d(to_map(-PI / 2., longitude), to_map(PI / 2., longitude), Color::rgb(0.2, 0.2, 0.2));
+    }
+    for n in -2..=2 {
+        let latitude = (n * 30) as f64 * PI / 180.;
+        let color = if n == 0 { Color::GRAY } else { Color::rgb(0.2, 0.2, 0.2) };
+        gizmos.line_2d(to_map(latitude, -PI), to_map(latitude, PI), color);
+    }
+
+    // crashed satellites show only the point of impact, escaped ones nothing
+    if let Some(Crashed(impact)) = crashed {
+        gizmos.circle_2d(to_map(impact.latitude, impact.longitude), 4., Color::RED);
+        return;
+    }
+    if escaped.is_some() {
+        return;
+    }
+
+    // track over the next orbits, until the patch about the current body ends or the next burn
+    let current_time = simulation_state.simulated_time;
+    let mean_conic = hierarchy.j2_precessed(sat.body, &sat.conic, current_time);
+    let t_end = (current_time + GROUND_TRACK_ORBITS * ground_track_orbit_time(&mean_conic))
+        .min(hierarchy.classify_patch(sat.body, &mean_conic, current_time).t_end)
+        .min(maneuver.map_or(f64::INFINITY, |node| node.t.max(current_time)));
+    let track: Vec<GroundTrackPoint> = hierarchy.ground_track(sat.body, &sat.conic, current_time, t_end, GROUND_TRACK_STEPS);
+    for segment in ground_track_segments(&track) {
+        gizmos.linestrip_2d(segment.iter().map(|point| to_map(point.latitude, point.longitude)), sat.color);
+    }
+
+    // sub-satellite point now
+    if let Some(point) = track.first() {
+        gizmos.circle_2d(to_map(point.latitude, point.longitude), 4., sat.color);
+    }
+}
@@ -0,0 +1,104 @@
+use bevy::math::DVec3;
+
+use super::{soi_entry_time_of, BodyHierarchy, OrbitConic};
+
+// point where a trajectory meets the surface of a body
+#[derive(Clone, Copy, Debug)]
+pub struct Impact {
+    pub t: f64, // simulation time of impact
+    pub body: usize, // index of the body hit within the body hierarchy
+    pub surface_position: DVec3, // in the body-fixed frame at the time of impact
+    pub latitude: f64,
+    pub longitude: f64,
+    pub speed: f64, // relative to the rotating surface
+}
+
+impl BodyHierarchy {
+
+    // first time within [t_start, t_end] that an object on conic about body idx reaches the
+    // body's surface, none for orbits whose periapsis clears it
+    pub fn next_impact(
+        &self,
+        idx: usize,
+        conic: &OrbitConic,
+        t_start: f64,
+        t_end: f64,
+    ) -> Option<Impact> {
+        let radius = self.bodies[idx].radius;
+        if radius <= 0. || conic.periapsis_radius() > radius {
+            return None;
+        }
+        soi_entry_time_of(|t| conic.state_at(t), radius, t_start, t_end, conic.revolution_time()).map(|t| {
+            let (pos, vel) = conic.state_at(t);
+            self.impact_at(idx, t, pos, vel)
+        })
+    }
+
+    // impact on body idx at simulation time t of an object with given state relative to it
+    pub fn impact_at(
+        &self,
+        idx: usize,
+        t: f64,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> Impact {
+        let (surface_position, surface_velocity) = self.body_fixed_frame(idx, t).to_frame(position, velocity);
+        let (latitude, longitude) = self.surface_coordinates(idx, t, position);
+        Impact {
+            t,
+            body: idx,
+            surface_position,
+            latitude,
+            longitude,
+            speed: surface_velocity.length(),
+        }
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::G;
+    use std::f64::consts::PI;
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_next_impact() {
+        let mut hierarchy = BodyHierarchy::new("Earth", 398600. / G, 6378., DVec3::Z);
+        hierarchy.bodies[0].rotation_period = 86164.;
+
+        // inclined ellipse from apoapsis with periapsis below the surface
+        let test_oc = OrbitConic::from_elements(
+            6700., 0.1, 0.5, 0., 0., PI, 398600. / G, DVec3::Z);
+        let impact = hierarchy.next_impact(0, &test_oc, 0., test_oc.period).unwrap();
+        assert_f!(test_oc.pos_at_t(impact.t).length(), 6378.);
+        assert!(impact.t < test_oc.period / 2.);
+        assert_f!(impact.surface_position.length(), 6378.);
+        assert!(impact.latitude.abs() <= 0.5 + 1e-9);
+
+        // surface position and speed from the body-fixed frame at the time of impact
+        let (pos, vel) = test_oc.state_at(impact.t);
+        let angle = 2. * PI * impact.t / 86164.;
+        let longitude = (pos.y.atan2(pos.x) - angle + PI).rem_euclid(2. * PI) - PI;
+        assert_f!(impact.longitude, longitude);
+        let surface_vel = DVec3::new(0., 0., 2. * PI / 86164.).cross(pos);
+        assert_f!(impact.speed, (vel - surface_vel).length());
+
+        // window ending before reaching the surface
+        assert!(hierarchy.next_impact(0, &test_oc, 0., impact.t - 1.).is_none());
+        // periapsis above the surface
+        let clear_oc = OrbitConic::from_elements(
+            7000., 0.05, 0.5, 0., 0., PI, 398600. / G, DVec3::Z);
+        assert!(hierarchy.next_impact(0, &clear_oc, 0., clear_oc.period).is_none());
+    }
+}
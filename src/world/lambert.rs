@@ -0,0 +1,252 @@
+use bevy::math::DVec3;
+use std::f64::consts::PI;
+
+use super::{bracketed_newton_solver, stumpff_c, stumpff_s, OrbitConic, G};
+
+// iterations of the golden section search for the minimum time of flight of a multi-revolution transfer
+const MIN_TOF_ITERATIONS: u32 = 100;
+// iterations of the bisection keeping hyperbolic transfers feasible
+const MAX_BRACKET_ITERATIONS: u32 = 200;
+
+// which way around the body the transfer travels between the two positions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LambertPath {
+    ShortWay, // transfer angle below 180°
+    LongWay, // transfer angle above 180°
+}
+
+impl LambertPath {
+
+    // path which travels counter-clockwise about the body's plane-of-reference normal
+    pub fn prograde(
+        r1: DVec3,
+        r2: DVec3,
+        body_plane_k: DVec3,
+    ) -> Self {
+        if r1.cross(r2).dot(body_plane_k) >= 0. {
+            LambertPath::ShortWay
+        } else {
+            LambertPath::LongWay
+        }
+    }
+}
+
+// solve lambert's problem, finding the conics from position r1 to position r2 in time of flight tof
+// after the given number of complete revolutions, using universal variables
+// https://orbital-mechanics.space/lamberts-problem/lamberts-problem.html
+//
+// zero revolutions gives exactly one solution, each additional revolution count gives either none
+// (tof is shorter than the minimum for that count) or two, ordered left branch then right branch.
+// returned conics have their initial state at r1, use with_epoch to place them in simulation time
+#[allow(clippy::too_many_arguments)]
+pub fn lambert_solver(
+    r1: DVec3,
+    r2: DVec3,
+    tof: f64,
+    path: LambertPath,
+    revolutions: u32,
+    body_mass: f64,
+    body_plane_k: DVec3,
+) -> Vec<OrbitConic> {
+    let mu = G * body_mass;
+    let sqrt_mu = mu.sqrt();
+    let r1_len = r1.length();
+    let r2_len = r2.length();
+
+    // transfer angle
+    let cos_dtheta = (r1.dot(r2) / (r1_len * r2_len)).clamp(-1., 1.);
+    let dtheta = match path {
+        LambertPath::ShortWay => f64::acos(cos_dtheta),
+        LambertPath::LongWay => 2. * PI - f64::acos(cos_dtheta),
+    };
+
+    // the transfer plane is undefined for collinear positions
+    let a = f64::sin(dtheta) * f64::sqrt(r1_len * r2_len / (1. - cos_dtheta));
+    if !a.is_finite() || a.abs() <= f64::EPSILON * (r1_len + r2_len) || tof <= 0. {
+        return Vec::new();
+    }
+
+    let y = |z: f64| {
+        r1_len + r2_len + a * (z * stumpff_s(z) - 1.) / stumpff_c(z).sqrt()
+    };
+    // time of flight equation, scaled by sqrt(mu), zero at the solution
+    let f = |z: f64| {
+        let y_z = y(z);
+        (y_z / stumpff_c(z)).powf(3. / 2.) * stumpff_s(z) + a * y_z.sqrt() - sqrt_mu * tof
+    };
+    let df = |z: f64| {
+        let y_z = y(z);
+        let c = stumpff_c(z);
+        let s = stumpff_s(z);
+        if z.abs() < 1e-9 {
+            let y_0 = y(0.);
+            f64::sqrt(2.) / 40. * y_0.powf(3. / 2.)
+                + a / 8. * (y_0.sqrt() + a * f64::sqrt(1. / (2. * y_0)))
+        } else {
+            (y_z / c).powf(3. / 2.) * (1. / (2. * z) * (c - 3. * s / (2. * c)) + 3. * s.powi(2) / (4. * c))
+                + a / 8. * (3. * s / c * y_z.sqrt() + a * (c / y_z).sqrt())
+        }
+    };
+
+    // conic leaving r1 for a given solution of z
+    let conic_at_z = |z: f64| {
+        let y_z = y(z);
+        let lagrange_f = 1. - y_z / r1_len;
+        let lagrange_g = a * f64::sqrt(y_z / mu);
+        let v1 = (r2 - lagrange_f * r1) / lagrange_g;
+        OrbitConic::from_initial(r1, v1, body_mass, body_plane_k)
+    };
+
+    // z is the square of the change in eccentric anomaly, so each revolution
+    // count has its own interval, with time of flight unbounded at both ends
+    let z_rev_lo = (2. * PI * revolutions as f64).powi(2);
+    let z_rev_hi = (2. * PI * (revolutions as f64 + 1.)).powi(2);
+    let z_hi = approach_asymptote(&f, z_rev_hi, z_rev_hi - z_rev_lo);
+
+    if revolutions == 0 {
+        // time of flight increases monotonically with z, widen towards hyperbolic
+        // transfers until tof is bracketed
+        let mut z_lo = 0.;
+        let mut z_feasible = 0.;
+        let mut step = 1.;
+        while y(z_lo) > 0. && f(z_lo) > 0. {
+            z_feasible = z_lo;
+            z_lo -= step;
+            step *= 2.;
+        }
+        // y must stay positive, if it was stepped past bisect back towards the
+        // last feasible z, time of flight goes to zero along with y
+        let mut z_infeasible = z_lo;
+        for _ in 0..MAX_BRACKET_ITERATIONS {
+            if y(z_lo) > 0. && f(z_lo) <= 0. {
+                break;
+            }
+            z_lo = (z_infeasible + z_feasible) / 2.;
+            if y(z_lo) <= 0. {
+                z_infeasible = z_lo;
+            } else if f(z_lo) > 0. {
+                z_feasible = z_lo;
+            }
+        }
+        vec![conic_at_z(bracketed_newton_solver(f, df, z_lo, z_hi, 0.))]
+    } else {
+        // a single minimum splits the interval into the left and right branches
+        let z_lo = approach_asymptote(&f, z_rev_lo, z_rev_lo - z_rev_hi);
+        let z_min = golden_section_min(&f, z_lo, z_hi);
+        if f(z_min) > 0. {
+            return Vec::new();
+        }
+        let z_left = bracketed_newton_solver(f, df, z_lo, z_min, (z_lo + z_min) / 2.);
+        let z_right = bracketed_newton_solver(f, df, z_min, z_hi, (z_min + z_hi) / 2.);
+        vec![conic_at_z(z_left), conic_at_z(z_right)]
+    }
+}
+
+// find a point between z_asymptote - gap and z_asymptote (where f goes to infinity) at which f is positive
+fn approach_asymptote(
+    f: &impl Fn(f64) -> f64,
+    z_asymptote: f64,
+    gap: f64,
+) -> f64 {
+    let mut gap = gap / 2.;
+    while f(z_asymptote - gap) <= 0. && gap.abs() > f64::EPSILON * z_asymptote.abs().max(1.) {
+        gap /= 2.;
+    }
+    z_asymptote - gap
+}
+
+// find the z of minimum f within [z_lo, z_hi], assuming a single minimum
+fn golden_section_min(
+    f: &impl Fn(f64) -> f64,
+    z_lo: f64,
+    z_hi: f64,
+) -> f64 {
+    let ratio = (5_f64.sqrt() - 1.) / 2.;
+    let mut lo = z_lo;
+    let mut hi = z_hi;
+    for _ in 0..MIN_TOF_ITERATIONS {
+        let z1 = hi - ratio * (hi - lo);
+        let z2 = lo + ratio * (hi - lo);
+        if f(z1) < f(z2) {
+            hi = z2;
+        } else {
+            lo = z1;
+        }
+    }
+    (lo + hi) / 2.
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lambert_example() {
+        // curtis example 5.2
+        let r1 = DVec3::new(5000., 10000., 2100.);
+        let r2 = DVec3::new(-14600., 2500., 7000.);
+        let path = LambertPath::prograde(r1, r2, DVec3::Z);
+        assert_eq!(path, LambertPath::ShortWay);
+        let conics = lambert_solver(r1, r2, 3600., path, 0, 398600. / G, DVec3::Z);
+        assert_eq!(conics.len(), 1);
+        let v1 = conics[0].initial_v;
+        assert_f!(v1.x, -5.9924946);
+        assert_f!(v1.y, 1.9253634);
+        assert_f!(v1.z, 3.2456365);
+        let (pos, vel) = conics[0].propagate(3600.);
+        assert_f!(pos.x, -14600.);
+        assert_f!(pos.y, 2500.);
+        assert_f!(pos.z, 7000.);
+        assert_f!(vel.x, -3.3124603);
+        assert_f!(vel.y, -4.1966173);
+        assert_f!(vel.z, -0.38528762);
+    }
+
+    #[test]
+    fn test_lambert_long_way_and_multi_revolution() {
+        let r1 = DVec3::new(7000., 0., 0.);
+        let r2 = DVec3::new(-3000., 9000., 0.);
+        let mass = 398600. / G;
+
+        // long way reaches r2 travelling clockwise
+        let conics = lambert_solver(r1, r2, 5000., LambertPath::LongWay, 0, mass, DVec3::Z);
+        assert_eq!(conics.len(), 1);
+        assert!(conics[0].h_vec.z < 0.);
+        let pos = conics[0].propagate(5000.).0;
+        assert_f!(pos.x, -3000.);
+        assert_f!(pos.y, 9000.);
+
+        // fast transfers are hyperbolic
+        let conics = lambert_solver(r1, r2, 300., LambertPath::ShortWay, 0, mass, DVec3::Z);
+        assert!(conics[0].e > 1.);
+        let pos = conics[0].propagate(300.).0;
+        assert_f!(pos.x, -3000.);
+        assert_f!(pos.y, 9000.);
+
+        // too short for a full revolution first
+        let conics = lambert_solver(r1, r2, 5000., LambertPath::ShortWay, 1, mass, DVec3::Z);
+        assert_eq!(conics.len(), 0);
+
+        // two branches, both arriving at r2 after one revolution
+        let conics = lambert_solver(r1, r2, 20000., LambertPath::ShortWay, 1, mass, DVec3::Z);
+        assert_eq!(conics.len(), 2);
+        for conic in conics.iter() {
+            assert!(conic.e < 1.);
+            assert!(conic.period < 20000.);
+            let pos = conic.propagate(20000.).0;
+            assert_f!(pos.x, -3000.);
+            assert_f!(pos.y, 9000.);
+        }
+        assert!(conics[0].period != conics[1].period);
+    }
+}
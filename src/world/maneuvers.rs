@@ -0,0 +1,111 @@
+use bevy::math::DVec3;
+use bevy::prelude::*;
+
+use super::OrbitConic;
+
+// impulsive burn of a satellite at a given simulation time, with delta-v
+// components in the orbit's local frame at the time of the burn
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ManeuverNode {
+    pub t: f64, // simulation time of the burn
+    pub prograde: f64, // delta-v along velocity
+    pub normal: f64, // delta-v along angular momentum
+    pub radial: f64, // delta-v outwards, perpendicular to velocity within the orbital plane
+}
+
+// burns after a satellite's current maneuver node in time order, the first becoming its node
+// once the current one is applied
+#[derive(Component, Clone, Debug, Default)]
+pub struct ManeuverQueue(pub Vec<ManeuverNode>);
+
+impl ManeuverNode {
+
+    // node at the next time after after_t that the conic reaches true anomaly nu
+    pub fn at_true_anomaly(
+        conic: &OrbitConic,
+        nu: f64,
+        after_t: f64,
+        prograde: f64,
+        normal: f64,
+        radial: f64,
+    ) -> Self {
+        let t_nu = conic.t_at_theta(nu - conic.initial_nu);
+        let t = if conic.e < 1. {
+            t_nu + ((after_t - t_nu) / conic.period).ceil() * conic.period
+        } else {
+            t_nu
+        };
+        ManeuverNode { t, prograde, normal, radial }
+    }
+
+    // calculate delta-v vector in the body's frame for a given state at the burn
+    pub fn delta_v_at(
+        &self,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> DVec3 {
+        let prograde_dir = velocity.normalize();
+        let normal_dir = position.cross(velocity).normalize();
+        let radial_dir = prograde_dir.cross(normal_dir);
+        self.prograde * prograde_dir + self.normal * normal_dir + self.radial * radial_dir
+    }
+
+    // calculate conic after applying the burn to the given conic
+    pub fn apply(
+        &self,
+        conic: &OrbitConic,
+    ) -> OrbitConic {
+        let (position, velocity) = conic.state_at(self.t);
+        OrbitConic::from_initial(
+            position,
+            velocity + self.delta_v_at(position, velocity),
+            conic.body_mass,
+            conic.body_plane_k)
+            .with_epoch(self.t)
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::G;
+    use std::f64::consts::PI;
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_maneuver_node_burns() {
+        let v_circular = f64::sqrt(398600. / 7000.);
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(7000., 0., 0.),
+            DVec3::new(0., v_circular, 0.),
+            398600. / G,
+            DVec3::Z,
+        );
+
+        // prograde burn at the start of the second revolution raises the opposite side
+        let node = ManeuverNode::at_true_anomaly(&test_oc, 0., 1., 1., 0., 0.);
+        assert_f!(node.t, test_oc.period);
+        let burn_oc = node.apply(&test_oc);
+        assert_f!(burn_oc.epoch, test_oc.period);
+        assert_f!(burn_oc.initial_r.x, 7000.);
+        assert_f!(burn_oc.initial_v.y, v_circular + 1.);
+        assert_f!(burn_oc.initial_nu + 0.1, 0. + 0.1);
+        assert!(burn_oc.r_at_theta(PI) > 7000.);
+
+        // normal and radial directions
+        let node = ManeuverNode { t: 0., prograde: 0., normal: 1., radial: 2. };
+        let dv = node.delta_v_at(test_oc.initial_r, test_oc.initial_v);
+        assert_f!(dv.z, 1.);
+        assert_f!(dv.x, 2.);
+    }
+}
@@ -1,6 +1,42 @@
+mod body_hierarchy;
+pub use body_hierarchy::*;
+mod encounters;
+pub use encounters::*;
+mod ephemeris;
+pub use ephemeris::*;
+mod equinoctial_elements;
+pub use equinoctial_elements::*;
+mod error_analysis;
+pub use error_analysis::*;
+mod flybys;
+pub use flybys::*;
+mod frames;
+pub use frames::*;
+mod ground_track;
+pub use ground_track::*;
+mod ground_track_map;
+pub use ground_track_map::*;
+mod impacts;
+pub use impacts::*;
+mod lambert;
+pub use lambert::*;
+mod maneuvers;
+pub use maneuvers::*;
+mod nbody;
+pub use nbody::*;
 mod newton_solver;
 pub use newton_solver::*;
 mod orbital_mechanics;
 pub use orbital_mechanics::*;
 mod orbits_demo;
 pub use orbits_demo::*;
+mod patches;
+pub use patches::*;
+mod perturbations;
+pub use perturbations::*;
+mod sphere_of_influence;
+pub use sphere_of_influence::*;
+mod tle;
+pub use tle::*;
+mod transfers;
+pub use transfers::*;
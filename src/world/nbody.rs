@@ -0,0 +1,274 @@
+use bevy::math::DVec3;
+use serde::Deserialize;
+
+use super::{j2_acceleration, BodyHierarchy, G};
+
+// numerical integration scheme for the n-body system
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum Integrator {
+    #[default]
+    Rk4, // classic fourth order runge-kutta
+    Leapfrog, // symplectic kick-drift-kick, bounded energy error over long runs
+}
+
+// J2 term of the gravity of an oblate body
+#[derive(Clone, Copy, Debug)]
+pub struct Oblateness {
+    pub j2: f64,
+    pub radius: f64, // equatorial radius
+    pub axis: DVec3, // axis of symmetry
+}
+
+// point masses in a shared inertial frame, each attracted by every other. bodies with zero mass
+// are test particles, such as satellites, which feel gravity but exert none. oblate bodies add
+// their J2 term to the attraction
+#[derive(Clone, Debug, Default)]
+pub struct NBodySystem {
+    pub masses: Vec<f64>,
+    pub positions: Vec<DVec3>,
+    pub velocities: Vec<DVec3>,
+    pub oblateness: Vec<Option<Oblateness>>,
+}
+
+impl NBodySystem {
+
+    // system of every body in the hierarchy at simulation time t, in the root's frame
+    pub fn from_hierarchy(
+        hierarchy: &BodyHierarchy,
+        t: f64,
+    ) -> Self {
+        let mut system = NBodySystem::default();
+        for (idx, body) in hierarchy.bodies.iter().enumerate() {
+            system.add_body(body.mass, hierarchy.pos_at_t(idx, t), hierarchy.vel_at_t(idx, t));
+            if body.j2 != 0. {
+                system.oblateness[idx] = Some(Oblateness { j2: body.j2, radius: body.radius, axis: body.body_plane_k });
+            }
+        }
+        system
+    }
+
+    // adds a point mass, returns its index
+    pub fn add_body(
+        &mut self,
+        mass: f64,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> usize {
+        self.masses.push(mass);
+        self.positions.push(position);
+        self.velocities.push(velocity);
+        self.oblateness.push(None);
+        self.masses.len() - 1
+    }
+
+    // adds a massless test particle, returns its index
+    pub fn add_particle(
+        &mut self,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> usize {
+        self.add_body(0., position, velocity)
+    }
+
+    // gravitational acceleration of each point for the given positions
+    pub fn accelerations(
+        &self,
+        positions: &[DVec3],
+    ) -> Vec<DVec3> {
+        let mut accelerations = vec![DVec3::ZERO; positions.len()];
+        for (j, &mass) in self.masses.iter().enumerate() {
+            if mass == 0. {
+                continue;
+            }
+            let mu = G * mass;
+            for (i, accel) in accelerations.iter_mut().enumerate() {
+                let offset = positions[j] - positions[i];
+                let distance_squared = offset.length_squared();
+                if i != j && distance_squared > 0. {
+                    *accel += mu * offset / (distance_squared * distance_squared.sqrt());
+                }
+            }
+        }
+
+        // J2 of oblate bodies on every other point, with the equal and opposite reaction on the
+        // body from massive points
+        for (j, oblateness) in self.oblateness.iter().enumerate() {
+            let Some(oblateness) = oblateness else {
+                continue;
+            };
+            for i in 0..positions.len() {
+                if i == j || positions[i] == positions[j] {
+                    continue;
+                }
+                let accel = j2_acceleration(positions[i] - positions[j], self.masses[j],
+                    oblateness.j2, oblateness.radius, oblateness.axis);
+                accelerations[i] += accel;
+                accelerations[j] -= accel * self.masses[i] / self.masses[j];
+            }
+        }
+        accelerations
+    }
+
+    // advance the system by dt
+    pub fn step(
+        &mut self,
+        dt: f64,
+        integrator: Integrator,
+    ) {
+        match integrator {
+            Integrator::Rk4 => self.step_rk4(dt),
+            Integrator::Leapfrog => self.step_leapfrog(dt),
+        }
+    }
+
+    // advance the system by dt in steps no longer than max_step
+    pub fn advance(
+        &mut self,
+        dt: f64,
+        max_step: f64,
+        integrator: Integrator,
+    ) {
+        let steps = (dt / max_step).ceil().max(1.);
+        for _ in 0..steps as u32 {
+            self.step(dt / steps, integrator);
+        }
+    }
+
+    fn step_rk4(
+        &mut self,
+        dt: f64,
+    ) {
+        // positions offset by the given derivative, scaled
+        let offset = |base: &[DVec3], derivative: &[DVec3], scale: f64| -> Vec<DVec3> {
+            base.iter().zip(derivative).map(|(x, dx)| *x + *dx * scale).collect()
+        };
+        let k1_v = self.velocities.clone();
+        let k1_a = self.accelerations(&self.positions);
+        let k2_v = offset(&self.velocities, &k1_a, dt / 2.);
+        let k2_a = self.accelerations(&offset(&self.positions, &k1_v, dt / 2.));
+        let k3_v = offset(&self.velocities, &k2_a, dt / 2.);
+        let k3_a = self.accelerations(&offset(&self.positions, &k2_v, dt / 2.));
+        let k4_v = offset(&self.velocities, &k3_a, dt);
+        let k4_a = self.accelerations(&offset(&self.positions, &k3_v, dt));
+        for i in 0..self.positions.len() {
+            self.positions[i] += dt / 6. * (k1_v[i] + 2. * k2_v[i] + 2. * k3_v[i] + k4_v[i]);
+            self.velocities[i] += dt / 6. * (k1_a[i] + 2. * k2_a[i] + 2. * k3_a[i] + k4_a[i]);
+        }
+    }
+
+    fn step_leapfrog(
+        &mut self,
+        dt: f64,
+    ) {
+        let accelerations = self.accelerations(&self.positions);
+        for ((pos, vel), accel) in self.positions.iter_mut().zip(&mut self.velocities).zip(accelerations) {
+            *vel += accel * dt / 2.;
+            *pos += *vel * dt;
+        }
+        let accelerations = self.accelerations(&self.positions);
+        for (vel, accel) in self.velocities.iter_mut().zip(accelerations) {
+            *vel += accel * dt / 2.;
+        }
+    }
+
+    // total kinetic and point mass potential energy of the massive bodies, conserved by exact
+    // integration without oblate bodies
+    pub fn energy(
+        &self,
+    ) -> f64 {
+        let mut energy = 0.;
+        for i in 0..self.masses.len() {
+            energy += 0.5 * self.masses[i] * self.velocities[i].length_squared();
+            for j in (i + 1)..self.masses.len() {
+                energy -= G * self.masses[i] * self.masses[j] / self.positions[i].distance(self.positions[j]);
+            }
+        }
+        energy
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{OrbitConic, SoiModel};
+    use std::f64::consts::PI;
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    // earth held at the origin with a satellite on an ellipse
+    fn two_body() -> (NBodySystem, OrbitConic) {
+        let conic = OrbitConic::from_initial(
+            DVec3::new(7000., 0., 0.),
+            DVec3::new(0., 9., 0.),
+            398600. / G,
+            DVec3::Z,
+        );
+        let mut system = NBodySystem::default();
+        system.add_body(398600. / G, DVec3::ZERO, DVec3::ZERO);
+        system.add_particle(conic.initial_r, conic.initial_v);
+        (system, conic)
+    }
+
+    #[test]
+    fn test_integrators_follow_conic() {
+        for integrator in [Integrator::Rk4, Integrator::Leapfrog] {
+            let (mut system, conic) = two_body();
+            system.advance(conic.period, 1., integrator);
+            // back at the start after one period
+            assert_f!(system.positions[1].x, 7000.);
+            assert_f!(system.velocities[1].y, 9.);
+            // the massive body is not pulled by the test particle
+            assert_eq!(system.positions[0], DVec3::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_nbody_energy() {
+        // two massive bodies about their barycenter
+        let mut system = NBodySystem::default();
+        system.add_body(398600. / G, DVec3::ZERO, DVec3::new(0., -0.0125, 0.));
+        system.add_body(4903. / G, DVec3::new(384400., 0., 0.), DVec3::new(0., 1.0185, 0.));
+        let energy = system.energy();
+        let momentum = system.velocities[0] * system.masses[0] + system.velocities[1] * system.masses[1];
+        system.advance(28. * 86400., 60., Integrator::Leapfrog);
+        assert_f!(system.energy(), energy);
+        let momentum_after = system.velocities[0] * system.masses[0] + system.velocities[1] * system.masses[1];
+        assert!((momentum_after - momentum).length() < 1e-9 * system.masses[0]);
+    }
+
+    #[test]
+    fn test_nbody_j2_drift() {
+        // inclined low orbit about an oblate earth, node regressing at the secular J2 rate
+        let mut hierarchy = BodyHierarchy::new("Earth", 398600. / G, 6378., DVec3::Z);
+        hierarchy.bodies[0].j2 = 1.08263e-3;
+        hierarchy.add_body("Satellite", 0, 1. / G, 0.,
+            DVec3::new(7000., 0., 0.), DVec3::new(0., 7.5 * 0.9_f64.cos(), 7.5 * 0.9_f64.sin()),
+            DVec3::Z, SoiModel::Laplace);
+        let conic = hierarchy.bodies[1].conic.unwrap();
+        let mut system = NBodySystem::from_hierarchy(&hierarchy, 0.);
+        assert!(system.oblateness[0].is_some() && system.oblateness[1].is_none());
+
+        // after whole revolutions, so the short periodic terms return to about where they began
+        let duration = 10. * conic.period;
+        system.advance(duration, 10., Integrator::Rk4);
+        let final_oc = OrbitConic::from_initial(
+            system.positions[1] - system.positions[0],
+            system.velocities[1] - system.velocities[0],
+            conic.body_mass + 1. / G,
+            DVec3::Z,
+        );
+        let (big_omega_rate, _) = conic.j2_rates(1.08263e-3, 6378.);
+        let drift = (final_oc.big_omega - conic.big_omega + PI).rem_euclid(2. * PI) - PI;
+        assert!(big_omega_rate < 0.);
+        assert!((drift - big_omega_rate * duration).abs() < 0.01 * (big_omega_rate * duration).abs());
+    }
+}
@@ -1,34 +1,80 @@
-const EPSILON: f32 = 0.000001;
+const EPSILON: f64 = 1e-12;
 const MAX_ITERATIONS: u32 = 20;
+const MAX_BRACKETED_ITERATIONS: u32 = 200;
 
 // approximate solution of given f,df using newton's method
+// converges when either f(x) or the step size (relative to x) is within EPSILON,
+// the latter for functions whose magnitude makes an absolute f(x) tolerance unreachable
 pub fn newton_solver(
-    f: impl Fn(f32) -> f32,
-    df: impl Fn(f32) -> f32,
-    x0: f32,
-) -> f32 {
+    f: impl Fn(f64) -> f64,
+    df: impl Fn(f64) -> f64,
+    x0: f64,
+) -> f64 {
     let mut x = x0;
     let mut delta = (0. - f(x)).abs();
+    let mut step = f64::INFINITY;
     let mut iterations = 0;
-    while delta > EPSILON && iterations < 10 {
-        x = x - f(x) / df(x);
+    while delta > EPSILON && step > EPSILON * x.abs().max(1.) && iterations < MAX_ITERATIONS {
+        let x1 = x - f(x) / df(x);
+        step = (x1 - x).abs();
+        x = x1;
         delta = (0. - f(x)).abs();
         iterations += 1;
     }
-    if iterations == MAX_ITERATIONS {
+    if delta > EPSILON && step > EPSILON * x.abs().max(1.) {
         panic!("newton solver failed to converge");
     }
     x
 }
 
+// approximate solution of given f,df within [x_lo, x_hi], where f changes sign,
+// taking newton steps from x0 but falling back to bisection whenever a step
+// would leave the bracket, so it cannot diverge
+pub fn bracketed_newton_solver(
+    f: impl Fn(f64) -> f64,
+    df: impl Fn(f64) -> f64,
+    x_lo: f64,
+    x_hi: f64,
+    x0: f64,
+) -> f64 {
+    let lo_sign = f(x_lo).signum();
+    let mut lo = x_lo;
+    let mut hi = x_hi;
+    let mut x = x0.clamp(lo, hi);
+    for _ in 0..MAX_BRACKETED_ITERATIONS {
+        let fx = f(x);
+        if fx.abs() <= EPSILON {
+            return x;
+        }
+        // shrink bracket around the root
+        if fx.signum() == lo_sign {
+            lo = x;
+        } else {
+            hi = x;
+        }
+        let newton_x = x - fx / df(x);
+        let next_x = if newton_x > lo && newton_x < hi {
+            newton_x
+        } else {
+            (lo + hi) / 2.
+        };
+        if (next_x - x).abs() <= EPSILON * x.abs().max(1.) {
+            return next_x;
+        }
+        x = next_x;
+    }
+    x
+}
+
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const TEST_EPSILON: f64 = 0.000001;
     macro_rules! assert_f {
         ($x:expr, $y:expr) => {
-            if !(($x - $y) < EPSILON && ($y - $x) < EPSILON) {
+            if !(($x - $y) < TEST_EPSILON && ($y - $x) < TEST_EPSILON) {
                 panic!("assert_f failed: {} !=> {}", $x, $y);
             }
         }
@@ -36,10 +82,10 @@ mod tests {
 
     #[test]
     fn test_newton_solver() {
-        let f = {|x: f32|
+        let f = {|x: f64|
             6. * x.powi(5) - 5. * x.powi(4) - 4. * x.powi(3) + 3. * x.powi(2)
         };
-        let df = {|x: f32|
+        let df = {|x: f64|
             30. * x.powi(4) - 20. * x.powi(3) - 12. * x.powi(2) + 6. * x
         };
 
@@ -58,4 +104,19 @@ mod tests {
         assert_f!(x, 1.);
         assert_f!(f(x), 0.);
     }
+
+    #[test]
+    fn test_bracketed_newton_solver() {
+        // plain newton's method cycles between -1 and 1 from x0 = 0 here
+        let f = {|x: f64|
+            x.powi(3) - 2. * x + 2.
+        };
+        let df = {|x: f64|
+            3. * x.powi(2) - 2.
+        };
+
+        let x = bracketed_newton_solver(f, df, -3., 3., 0.);
+        assert_f!(x, -1.769292);
+        assert_f!(f(x), 0.);
+    }
 }
@@ -1,26 +1,35 @@
-use bevy::prelude::*;
-use std::f32::consts::PI;
+use bevy::math::DVec3;
+use std::f64::consts::PI;
 
-use super::newton_solver;
+use super::{bracketed_newton_solver, newton_solver};
 
 // gravitational constant
-pub const G: f32 = 6.67408e-11;
+pub const G: f64 = 6.67408e-11;
+
+// eccentricity distance from 1. within which a conic is treated as parabolic
+const PARABOLIC_EPSILON: f64 = 1e-9;
+// eccentricity (and node vector length relative to h) below which a conic is
+// treated as circular (or equatorial) and its undefined angles fall back
+const SINGULAR_EPSILON: f64 = 1e-9;
 
 // struct for classical orbital elements of orbit conic of small satellite
 // https://orbital-mechanics.space/classical-orbital-elements/orbital-elements-and-the-state-vector.html
 #[derive(Clone, Copy, Debug)]
 pub struct OrbitConic {
-    pub body_mass: f32, // mass of barycenter/body
-    pub initial_r: Vec3, // initial position relative to barycenter
-    pub h_vec: Vec3, // vector perpendicular to orbital plane
-    pub h: f32, // angular momentum
-    pub i: f32, // inclination
-    pub big_omega: f32, // right ascension of ascending node
-    pub e: f32, // eccentricity
-    pub e_vec: Vec3, // eccentricity vector
-    pub omega: f32, // argument of periapsis
-    pub initial_nu: f32, // initial true anomoly
-    pub period: f32, // orbit period
+    pub body_mass: f64, // mass of barycenter/body
+    pub body_plane_k: DVec3, // normal of body's plane-of-reference
+    pub initial_r: DVec3, // initial position relative to barycenter
+    pub initial_v: DVec3, // initial velocity relative to barycenter
+    pub h_vec: DVec3, // vector perpendicular to orbital plane
+    pub h: f64, // angular momentum
+    pub i: f64, // inclination
+    pub big_omega: f64, // right ascension of ascending node
+    pub e: f64, // eccentricity
+    pub e_vec: DVec3, // eccentricity vector
+    pub omega: f64, // argument of periapsis
+    pub initial_nu: f64, // initial true anomoly
+    pub period: f64, // orbit period
+    pub epoch: f64, // simulation time of the initial state
 }
 
 impl OrbitConic {
@@ -28,38 +37,46 @@ impl OrbitConic {
     // initializes conic parameters from initial position, velocity,
     // and body parameters
     pub fn from_initial(
-        position: Vec3,
-        velocity: Vec3,
-        body_mass: f32,
-        body_plane_k: Vec3,
+        position: DVec3,
+        velocity: DVec3,
+        body_mass: f64,
+        body_plane_k: DVec3,
     ) -> Self {
         // angular momentum
         let h_vec = position.cross(velocity);
         let h = h_vec.length();
 
         // inclination relative to body's plane-of-reference
-        let i = f32::acos(h_vec.dot(body_plane_k) / h);
+        let i = f64::acos((h_vec.dot(body_plane_k) / h).clamp(-1., 1.));
 
-        // right ascension of ascending node
+        // right ascension of ascending node, the node line is undefined for
+        // equatorial orbits so the reference direction is used with big_omega = 0
+        let (i_hat, _) = reference_basis(body_plane_k);
         let n_vec = body_plane_k.cross(h_vec);
-        let n = n_vec.length();
-        let big_omega = 2. * PI - f32::acos(n_vec.x / n);
+        let equatorial = n_vec.length() <= SINGULAR_EPSILON * h;
+        let node_dir = if equatorial { i_hat } else { n_vec.normalize() };
+        let big_omega = angle_between(i_hat, node_dir, body_plane_k);
 
-        // eccentricity
+        // eccentricity, periapsis is undefined for circular orbits so the
+        // initial position is used as the periapsis direction instead
         let mu = G * body_mass;
         let e_vec = velocity.cross(h_vec) / mu - position.normalize();
+        let circular = e_vec.length() <= SINGULAR_EPSILON;
+        let e_vec = if circular { DVec3::ZERO } else { e_vec };
         let e = e_vec.length();
+        let periapsis_dir = if circular { position.normalize() } else { e_vec.normalize() };
 
-        // argument of periapsis
-        let omega = 2. * PI - f32::acos(n_vec.dot(e_vec) / (n * e));
+        // argument of periapsis, which becomes the longitude of periapsis for equatorial
+        // orbits and the argument of latitude (or true longitude) for circular orbits
+        let omega = angle_between(node_dir, periapsis_dir, h_vec);
 
         // initial true anomaly, from -180. to 180.
-        let initial_nu = if e_vec.length() <= 0. {
+        let initial_nu = if circular {
             0.
         } else if 0. > e_vec.cross(position).dot(h_vec) {
-            -f32::acos(position.normalize().dot(e_vec.normalize()))
+            -f64::acos(position.normalize().dot(e_vec.normalize()).clamp(-1., 1.))
         } else {
-            f32::acos(position.normalize().dot(e_vec.normalize()))
+            f64::acos(position.normalize().dot(e_vec.normalize()).clamp(-1., 1.))
         };
 
         // period
@@ -69,7 +86,9 @@ impl OrbitConic {
         // return
         OrbitConic {
             body_mass,
+            body_plane_k,
             initial_r: position,
+            initial_v: velocity,
             h_vec,
             h,
             i,
@@ -79,75 +98,301 @@ impl OrbitConic {
             omega,
             initial_nu,
             period,
+            epoch: 0.,
+        }
+    }
+
+    // sets the simulation time at which the initial state applies
+    pub fn with_epoch(
+        mut self,
+        epoch: f64,
+    ) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    // initializes conic from classical orbital elements, semi-major axis a (negative for
+    // hyperbolas), eccentricity, inclination, right ascension of ascending node, argument of
+    // periapsis and initial true anomaly, relative to the body's plane-of-reference
+    // https://orbital-mechanics.space/classical-orbital-elements/orbital-elements-and-the-state-vector.html
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_elements(
+        a: f64,
+        e: f64,
+        i: f64,
+        big_omega: f64,
+        omega: f64,
+        nu: f64,
+        body_mass: f64,
+        body_plane_k: DVec3,
+    ) -> Self {
+        let mu = G * body_mass;
+        let h = f64::sqrt(mu * a * (1. - e.powi(2)));
+        let (position, velocity) = state_from_elements(mu, h, e, i, big_omega, omega, nu, body_plane_k);
+        OrbitConic::from_initial(position, velocity, body_mass, body_plane_k)
+    }
+
+    // calculate initial position and velocity from the classical orbital elements,
+    // the inverse of from_initial
+    pub fn to_state_vector(
+        &self,
+    ) -> (DVec3, DVec3) {
+        let mu = G * self.body_mass;
+        state_from_elements(mu, self.h, self.e, self.i, self.big_omega, self.omega,
+            self.initial_nu, self.body_plane_k)
+    }
+
+    // whether periapsis is undefined, in which case omega is measured to the initial position
+    pub fn is_circular(
+        &self,
+    ) -> bool {
+        self.e_vec.length() <= 0.
+    }
+
+    // whether the ascending node is undefined, in which case big_omega is 0.
+    pub fn is_equatorial(
+        &self,
+    ) -> bool {
+        self.body_plane_k.cross(self.h_vec).length() <= SINGULAR_EPSILON * self.h
+    }
+
+    // longitude of periapsis, angle from the reference direction to periapsis,
+    // well-defined for equatorial orbits
+    pub fn longitude_of_periapsis(
+        &self,
+    ) -> f64 {
+        (self.big_omega + self.omega).rem_euclid(2. * PI)
+    }
+
+    // argument of latitude for given true anomaly, angle from the ascending node,
+    // well-defined for circular orbits
+    pub fn argument_of_latitude(
+        &self,
+        nu: f64,
+    ) -> f64 {
+        (self.omega + nu).rem_euclid(2. * PI)
+    }
+
+    // true longitude for given true anomaly, angle from the reference direction,
+    // well-defined for circular equatorial orbits
+    pub fn true_longitude(
+        &self,
+        nu: f64,
+    ) -> f64 {
+        (self.big_omega + self.omega + nu).rem_euclid(2. * PI)
+    }
+
+    // semi-major axis, negative for hyperbolas and infinite for parabolas
+    pub fn semi_major_axis(
+        &self,
+    ) -> f64 {
+        self.semi_latus_rectum() / (1. - self.e.powi(2))
+    }
+
+    // semi-latus rectum
+    pub fn semi_latus_rectum(
+        &self,
+    ) -> f64 {
+        self.h.powi(2) / (G * self.body_mass)
+    }
+
+    // distance from body center at periapsis
+    pub fn periapsis_radius(
+        &self,
+    ) -> f64 {
+        self.semi_latus_rectum() / (1. + self.e)
+    }
+
+    // distance from body center at apoapsis, infinite for open orbits
+    pub fn apoapsis_radius(
+        &self,
+    ) -> f64 {
+        if self.e < 1. {
+            self.semi_latus_rectum() / (1. - self.e)
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    // height of periapsis above the surface of a body with given radius
+    pub fn periapsis_altitude(
+        &self,
+        body_radius: f64,
+    ) -> f64 {
+        self.periapsis_radius() - body_radius
+    }
+
+    // height of apoapsis above the surface of a body with given radius
+    pub fn apoapsis_altitude(
+        &self,
+        body_radius: f64,
+    ) -> f64 {
+        self.apoapsis_radius() - body_radius
+    }
+
+    // specific orbital energy, negative for closed orbits
+    pub fn specific_energy(
+        &self,
+    ) -> f64 {
+        let mu = G * self.body_mass;
+        -mu.powi(2) / (2. * self.h.powi(2)) * (1. - self.e.powi(2))
+    }
+
+    // mean motion, the rate of the mean anomaly used by t_at_nu and nu_at_t
+    pub fn mean_motion(
+        &self,
+    ) -> f64 {
+        let mu = G * self.body_mass;
+        if (self.e - 1.).abs() < PARABOLIC_EPSILON {
+            f64::sqrt(mu / self.semi_latus_rectum().powi(3))
+        } else {
+            f64::sqrt(mu / self.semi_major_axis().abs().powi(3))
+        }
+    }
+
+    // time scale of one revolution, the period of closed conics, and for open ones the same
+    // from the mean motion, the time to cover the semi-major axis at about the excess speed
+    pub fn revolution_time(
+        &self,
+    ) -> f64 {
+        if self.e < 1. {
+            self.period
+        } else {
+            2. * PI / self.mean_motion()
+        }
+    }
+
+    // hyperbolic excess velocity, speed remaining far from the body on an open orbit
+    pub fn v_inf(
+        &self,
+    ) -> Option<f64> {
+        if self.e >= 1. {
+            Some(f64::sqrt(2. * self.specific_energy().max(0.)))
+        } else {
+            None
+        }
+    }
+
+    // turning angle between the incoming and outgoing asymptotes of an open orbit
+    pub fn turning_angle(
+        &self,
+    ) -> Option<f64> {
+        if self.e >= 1. {
+            Some(2. * f64::asin(1. / self.e))
+        } else {
+            None
+        }
+    }
+
+    // time from simulation time t until the next periapsis passage,
+    // none if an open orbit has already passed periapsis
+    pub fn time_to_periapsis(
+        &self,
+        t: f64,
+    ) -> Option<f64> {
+        let t_since = self.t_at_nu(self.nu_at_pos(self.pos_at_t(t)));
+        if self.e < 1. {
+            Some((-t_since).rem_euclid(self.period))
+        } else if t_since <= 0. {
+            Some(-t_since)
+        } else {
+            None
+        }
+    }
+
+    // time from simulation time t until the next apoapsis passage, none for open orbits
+    pub fn time_to_apoapsis(
+        &self,
+        t: f64,
+    ) -> Option<f64> {
+        if self.e < 1. {
+            let t_since = self.t_at_nu(self.nu_at_pos(self.pos_at_t(t)));
+            Some((self.period / 2. - t_since).rem_euclid(self.period))
+        } else {
+            None
         }
     }
 
     // calculate distance from body for given angle from periapsis (true anomaly)
     pub fn r_at_theta(
         &self,
-        theta: f32,
-    ) -> f32{
-        self.h.powi(2) / (G * self.body_mass * (1. + self.e * f32::cos(self.initial_nu + theta)))
+        theta: f64,
+    ) -> f64{
+        self.h.powi(2) / (G * self.body_mass * (1. + self.e * f64::cos(self.initial_nu + theta)))
+    }
+
+    // perifocal basis: towards periapsis (the initial position for circular orbits),
+    // 90° ahead of it in the orbital plane, and along angular momentum
+    pub fn perifocal_basis(
+        &self,
+    ) -> (DVec3, DVec3, DVec3) {
+        let p_hat = if self.is_circular() { self.initial_r.normalize() } else { self.e_vec.normalize() };
+        let w_hat = self.h_vec.normalize();
+        (p_hat, w_hat.cross(p_hat), w_hat)
     }
 
     // calculate orientation relative to parent body's center
     pub fn dir_at_theta(
         &self,
-        theta: f32,
-    ) -> Vec3 {
-        let x_vec = self.h_vec.cross(self.initial_r).normalize();
-        let z_vec = self.initial_r.normalize();
-        x_vec * f32::sin(theta) + z_vec * f32::cos(theta)
+        theta: f64,
+    ) -> DVec3 {
+        let (p_hat, q_hat, _) = self.perifocal_basis();
+        let nu = self.initial_nu + theta;
+        p_hat * f64::cos(nu) + q_hat * f64::sin(nu)
     }
 
     // calculate position relative to body center
     pub fn pos_at_theta(
         &self,
-        theta: f32,
-    ) -> Vec3 {
+        theta: f64,
+    ) -> DVec3 {
         self.r_at_theta(theta) * self.dir_at_theta(theta)
     }
 
     // calculate velocity for a given anomaly
     pub fn vel_at_theta(
         &self,
-        theta: f32,
-    ) -> Vec3 {
+        theta: f64,
+    ) -> DVec3 {
         // v_w = mu / h * np.array((-np.sin(nu), e + np.cos(nu), 0))
         let t_vel = G * self.body_mass / self.h;
-        let nu_r = self.dir_at_theta(-self.initial_nu);
-        let z_vec = self.h_vec.cross(nu_r).normalize();
-        let x_vec = nu_r.normalize();
-        let v_dir = x_vec * -f32::sin(self.initial_nu + theta) + z_vec * (self.e + f32::cos(self.initial_nu + theta));
+        let (p_hat, q_hat, _) = self.perifocal_basis();
+        let v_dir = p_hat * -f64::sin(self.initial_nu + theta) + q_hat * (self.e + f64::cos(self.initial_nu + theta));
         t_vel * v_dir
     }
 
     // calculate change in velocity for given position relative to body center
     pub fn dv_at_pos(
         &self,
-        rel_pos: Vec3,
-    ) -> Vec3 {
+        rel_pos: DVec3,
+    ) -> DVec3 {
         G * self.body_mass * -rel_pos.normalize() / rel_pos.length_squared()
     }
     // calculate time at given anomaly
     pub fn t_at_nu(
         &self,
-        nu: f32,
-    ) -> f32 {
+        nu: f64,
+    ) -> f64 {
         let mu = G * self.body_mass;
+        // parabolic, barker's equation
+        if (self.e - 1.).abs() < PARABOLIC_EPSILON {
+            let tan_nu = f64::tan(nu / 2.);
+            let mp_nu = tan_nu / 2. + tan_nu.powi(3) / 6.;
+            mp_nu * self.h.powi(3) / mu.powi(2)
+
         // elliptical
-        if self.e < 1. {
+        } else if self.e < 1. {
             // mean anomoly
-            let me_nu = 2. * f32::atan(f32::sqrt((1. - self.e) / (1. + self.e)) * f32::tan(nu / 2.))
-                - (self.e * f32::sqrt(1. - self.e.powi(2)) * f32::sin(nu)) / (1. + self.e * f32::cos(nu));
+            let me_nu = 2. * f64::atan(f64::sqrt((1. - self.e) / (1. + self.e)) * f64::tan(nu / 2.))
+                - (self.e * f64::sqrt(1. - self.e.powi(2)) * f64::sin(nu)) / (1. + self.e * f64::cos(nu));
             // t
             me_nu * self.h.powi(3) / (mu.powi(2) * (1. - self.e.powi(2)).powf(3./2.))
 
         // hyperbolic
         } else {
-            let mh_nu = (self.e * f32::sqrt(self.e.powi(2) - 1.) * f32::sin(nu)) / (1. + self.e * f32::cos(nu))
-                - f32::ln((f32::sqrt(self.e + 1.) + f32::sqrt(self.e - 1.) * f32::tan(nu/2.))
-                    / (f32::sqrt(self.e + 1.) - f32::sqrt(self.e - 1.) * f32::tan(nu/2.)));
+            let mh_nu = (self.e * f64::sqrt(self.e.powi(2) - 1.) * f64::sin(nu)) / (1. + self.e * f64::cos(nu))
+                - f64::ln((f64::sqrt(self.e + 1.) + f64::sqrt(self.e - 1.) * f64::tan(nu/2.))
+                    / (f64::sqrt(self.e + 1.) - f64::sqrt(self.e - 1.) * f64::tan(nu/2.)));
             mh_nu * self.h.powi(3) / (mu.powi(2) * (self.e.powi(2) - 1.).powf(3./2.))
         }
     }
@@ -155,34 +400,40 @@ impl OrbitConic {
     // calculate true anomaly of position
     pub fn nu_at_pos(
         &self,
-        position: Vec3,
-    ) -> f32 {
-        if self.e_vec.length() <= 0. {
-            0.
-        } else if 0. > self.e_vec.cross(position).dot(self.h_vec) {
-            -f32::acos(position.normalize().dot(self.e_vec.normalize()))
+        position: DVec3,
+    ) -> f64 {
+        // circular orbits measure from the initial position
+        let (periapsis_dir, _, _) = self.perifocal_basis();
+        if 0. > periapsis_dir.cross(position).dot(self.h_vec) {
+            -f64::acos(position.normalize().dot(periapsis_dir).clamp(-1., 1.))
         } else {
-            f32::acos(position.normalize().dot(self.e_vec.normalize()))
+            f64::acos(position.normalize().dot(periapsis_dir).clamp(-1., 1.))
         }
     }
 
     // calculate true anomaly at time t
     pub fn nu_at_t(
         &self,
-        t: f32,
-    ) -> f32 {
+        t: f64,
+    ) -> f64 {
         let mu = G * self.body_mass;
 
+        // parabolic, closed form inverse of barker's equation
+        if (self.e - 1.).abs() < PARABOLIC_EPSILON {
+            let mp_nu = mu.powi(2) / self.h.powi(3) * t;
+            let z = (3. * mp_nu + (9. * mp_nu.powi(2) + 1.).sqrt()).cbrt();
+            2. * f64::atan(z - 1. / z)
+
         // elliptical
-        if self.e < 1. {
-            let me_nu = 2. * PI * t / self.period;
+        } else if self.e < 1. {
+            let me_nu = (2. * PI * t / self.period).rem_euclid(2. * PI);
 
             // use newton's method to solve for eccentric anomaly
             let e = self.e;
-            let f = {|x: f32|
+            let f = {|x: f64|
                 x - e * x.sin() - me_nu
             };
-            let df = {|x: f32|
+            let df = {|x: f64|
                 1. - e * x.cos()
             };
             let ec_nu = newton_solver(f, df, PI);
@@ -196,19 +447,232 @@ impl OrbitConic {
 
             // use newton's method to solve for eccentric anomaly
             let e = self.e;
-            let f = {|x: f32|
+            let f = {|x: f64|
                 e * x.sinh() - x - me_nu
             };
-            let df = {|x: f32|
+            let df = {|x: f64|
                 e * x.cosh() - 1.
             };
-            let ec_nu = newton_solver(f, df, PI);
+            let ec_nu = newton_solver(f, df, (me_nu / e).asinh());
 
             let nu = 2. * (((e + 1.) / (e - 1.)).sqrt() * (ec_nu / 2.).tanh()).atan();
             nu
         }
     }
 
+    // solve the universal kepler equation for the universal anomaly dt after the initial state
+    // https://orbital-mechanics.space/time-since-periapsis-and-keplers-equation/universal-variables.html
+    pub fn chi_at_dt(
+        &self,
+        dt: f64,
+    ) -> f64 {
+        let mu = G * self.body_mass;
+        let sqrt_mu = mu.sqrt();
+        let r0 = self.initial_r.length();
+        let vr0 = self.initial_r.dot(self.initial_v) / r0;
+        // reciprocal of semi-major axis, positive for ellipses, zero for parabolas
+        let alpha = 2. / r0 - self.initial_v.length_squared() / mu;
+
+        // closed orbits repeat, so only the remainder of the period needs solving
+        let dt = if alpha > 0. {
+            let period = 2. * PI / (sqrt_mu * alpha.powf(3. / 2.));
+            dt % period
+        } else {
+            dt
+        };
+
+        let f = |x: f64| {
+            let z = alpha * x.powi(2);
+            r0 * vr0 / sqrt_mu * x.powi(2) * stumpff_c(z)
+                + (1. - alpha * r0) * x.powi(3) * stumpff_s(z)
+                + r0 * x - sqrt_mu * dt
+        };
+        let df = |x: f64| {
+            let z = alpha * x.powi(2);
+            r0 * vr0 / sqrt_mu * x * (1. - z * stumpff_s(z))
+                + (1. - alpha * r0) * x.powi(2) * stumpff_c(z)
+                + r0
+        };
+
+        // starting guess depending on conic type, the hyperbolic estimate
+        // falls back to zero where its logarithm is undefined
+        let x0 = if alpha > 0. {
+            sqrt_mu * alpha * dt
+        } else if alpha < 0. && dt != 0. {
+            let a = 1. / alpha;
+            let sign = dt.signum();
+            sign * (-a).sqrt() * f64::ln((-2. * mu * alpha * dt)
+                / (r0 * vr0 + sign * (-mu * a).sqrt() * (1. - r0 * alpha))).max(0.)
+        } else {
+            sqrt_mu * dt / r0
+        };
+
+        // f increases monotonically with chi (its derivative is the radius),
+        // so widen from the starting guess until the root is bracketed
+        let mut step = (sqrt_mu * dt.abs() / r0).max(f64::EPSILON);
+        let mut x_lo = x0;
+        while f(x_lo) > 0. {
+            x_lo -= step;
+            step *= 2.;
+        }
+        let mut x_hi = x0;
+        while f(x_hi) < 0. {
+            x_hi += step;
+            step *= 2.;
+        }
+        bracketed_newton_solver(f, df, x_lo, x_hi, x0)
+    }
+
+    // calculate position and velocity at simulation time t
+    pub fn state_at(
+        &self,
+        t: f64,
+    ) -> (DVec3, DVec3) {
+        self.propagate(t - self.epoch)
+    }
+
+    // calculate position relative to body center at simulation time t
+    pub fn pos_at_t(
+        &self,
+        t: f64,
+    ) -> DVec3 {
+        self.state_at(t).0
+    }
+
+    // calculate velocity at simulation time t
+    pub fn vel_at_t(
+        &self,
+        t: f64,
+    ) -> DVec3 {
+        self.state_at(t).1
+    }
+
+    // calculate simulation time at which the body has swept angle theta from its initial
+    // position, counting whole revolutions of closed orbits
+    pub fn t_at_theta(
+        &self,
+        theta: f64,
+    ) -> f64 {
+        if self.e < 1. {
+            let revolutions = theta.div_euclid(2. * PI);
+            let dt = self.t_at_nu(self.initial_nu + theta.rem_euclid(2. * PI))
+                - self.t_at_nu(self.initial_nu);
+            self.epoch + revolutions * self.period + dt.rem_euclid(self.period)
+        } else {
+            self.epoch + self.t_at_nu(self.initial_nu + theta) - self.t_at_nu(self.initial_nu)
+        }
+    }
+
+    // calculate position and velocity dt after the initial state using lagrange coefficients of
+    // the universal anomaly, valid for elliptic, parabolic and hyperbolic conics alike
+    pub fn propagate(
+        &self,
+        dt: f64,
+    ) -> (DVec3, DVec3) {
+        let mu = G * self.body_mass;
+        let sqrt_mu = mu.sqrt();
+        let r0 = self.initial_r.length();
+        let alpha = 2. / r0 - self.initial_v.length_squared() / mu;
+        let chi = self.chi_at_dt(dt);
+        let z = alpha * chi.powi(2);
+
+        // the solver works on the remainder of the period for closed orbits
+        let dt = if alpha > 0. {
+            dt % (2. * PI / (sqrt_mu * alpha.powf(3. / 2.)))
+        } else {
+            dt
+        };
+
+        // lagrange coefficients
+        let f = 1. - chi.powi(2) / r0 * stumpff_c(z);
+        let g = dt - chi.powi(3) / sqrt_mu * stumpff_s(z);
+        let position = f * self.initial_r + g * self.initial_v;
+        let r = position.length();
+        let df = sqrt_mu / (r * r0) * (z * chi * stumpff_s(z) - chi);
+        let dg = 1. - chi.powi(2) / r * stumpff_c(z);
+        let velocity = df * self.initial_r + dg * self.initial_v;
+
+        (position, velocity)
+    }
+
+}
+
+// reference direction and its perpendicular within the body's plane-of-reference,
+// x (projected onto the plane) is used as the reference direction, or y for planes normal to x
+pub fn reference_basis(
+    body_plane_k: DVec3,
+) -> (DVec3, DVec3) {
+    let reference = if body_plane_k.cross(DVec3::X).length() > SINGULAR_EPSILON { DVec3::X } else { DVec3::Y };
+    let i_hat = (reference - body_plane_k * reference.dot(body_plane_k)).normalize();
+    let j_hat = body_plane_k.cross(i_hat);
+    (i_hat, j_hat)
+}
+
+// angle from direction a to direction b, counter-clockwise about axis, from 0. to 360.
+pub fn angle_between(
+    a: DVec3,
+    b: DVec3,
+    axis: DVec3,
+) -> f64 {
+    f64::atan2(a.cross(b).dot(axis.normalize()), a.dot(b)).rem_euclid(2. * PI)
+}
+
+// calculate position and velocity from angular momentum and angular elements by
+// rotating the perifocal state into the body's reference frame
+#[allow(clippy::too_many_arguments)]
+fn state_from_elements(
+    mu: f64,
+    h: f64,
+    e: f64,
+    i: f64,
+    big_omega: f64,
+    omega: f64,
+    nu: f64,
+    body_plane_k: DVec3,
+) -> (DVec3, DVec3) {
+    let (i_hat, j_hat) = reference_basis(body_plane_k);
+
+    // perifocal basis, p towards periapsis and q 90° ahead in the direction of motion
+    let (sin_o, cos_o) = big_omega.sin_cos();
+    let (sin_w, cos_w) = omega.sin_cos();
+    let (sin_i, cos_i) = i.sin_cos();
+    let p_hat = (cos_o * cos_w - sin_o * sin_w * cos_i) * i_hat
+        + (sin_o * cos_w + cos_o * sin_w * cos_i) * j_hat
+        + (sin_w * sin_i) * body_plane_k;
+    let q_hat = (-cos_o * sin_w - sin_o * cos_w * cos_i) * i_hat
+        + (-sin_o * sin_w + cos_o * cos_w * cos_i) * j_hat
+        + (cos_w * sin_i) * body_plane_k;
+
+    let r = h.powi(2) / mu / (1. + e * nu.cos());
+    let position = r * (nu.cos() * p_hat + nu.sin() * q_hat);
+    let velocity = mu / h * (-nu.sin() * p_hat + (e + nu.cos()) * q_hat);
+    (position, velocity)
+}
+
+// stumpff function c(z), using the series expansion near zero to avoid cancellation
+pub fn stumpff_c(
+    z: f64,
+) -> f64 {
+    if z > 1e-3 {
+        (1. - z.sqrt().cos()) / z
+    } else if z < -1e-3 {
+        ((-z).sqrt().cosh() - 1.) / -z
+    } else {
+        1. / 2. - z / 24. + z.powi(2) / 720. - z.powi(3) / 40320.
+    }
+}
+
+// stumpff function s(z), using the series expansion near zero to avoid cancellation
+pub fn stumpff_s(
+    z: f64,
+) -> f64 {
+    if z > 1e-3 {
+        (z.sqrt() - z.sqrt().sin()) / z.powf(3. / 2.)
+    } else if z < -1e-3 {
+        ((-z).sqrt().sinh() - (-z).sqrt()) / (-z).powf(3. / 2.)
+    } else {
+        1. / 6. - z / 120. + z.powi(2) / 5040. - z.powi(3) / 362880.
+    }
 }
 
 
@@ -216,7 +680,7 @@ impl OrbitConic {
 mod tests {
     use super::*;
 
-    const EPSILON: f32 = 0.000001;
+    const EPSILON: f64 = 0.000001;
     macro_rules! assert_f {
         ($x:expr, $y:expr) => {
             if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
@@ -233,10 +697,10 @@ mod tests {
     #[test]
     fn test_example_orbit() {
         let test_oc = OrbitConic::from_initial(
-            Vec3::new(1000., 5000., 7000.),
-            Vec3::new(3., 4., 5.),
+            DVec3::new(1000., 5000., 7000.),
+            DVec3::new(3., 4., 5.),
             398600. / G,
-            Vec3::Z,
+            DVec3::Z,
         );
 
         assert_f!(test_oc.h, 19646.883);
@@ -251,10 +715,10 @@ mod tests {
     #[test]
     fn test_orbit_fns_circular() {
         let test_oc = OrbitConic::from_initial(
-            Vec3::new(0., 0., 1000.),
-            Vec3::new(19.96497, 0., 0.),
+            DVec3::new(0., 0., 1000.),
+            DVec3::new(19.96497, 0., 0.),
             398600. / G,
-            Vec3::Y,
+            DVec3::Y,
         );
         assert_f!(test_oc.e + 0.1, 0. + 0.1);
         assert_f!(test_oc.initial_nu + 0.1, 0. + 0.1);
@@ -276,10 +740,10 @@ mod tests {
         assert_f!(vel_3.z, 19.96497);
 
         let test_oc2 = OrbitConic::from_initial(
-            Vec3::new(1000., 0., 0.),
-            Vec3::new(0., 0., -19.96497),
+            DVec3::new(1000., 0., 0.),
+            DVec3::new(0., 0., -19.96497),
             398600. / G,
-            Vec3::Y,
+            DVec3::Y,
         );
         assert_f!(test_oc2.e + 0.1, 0. + 0.1);
         assert_f!(test_oc2.initial_nu + 0.1, 0. + 0.1);
@@ -304,10 +768,10 @@ mod tests {
     #[test]
     fn test_orbit_fns_ellipse_apoapsis_start() {
         let test_oc3 = OrbitConic::from_initial(
-            Vec3::new(1000., 0., 0.),
-            Vec3::new(0., 0., -14.),
+            DVec3::new(1000., 0., 0.),
+            DVec3::new(0., 0., -14.),
             398600. / G,
-            Vec3::Y,
+            DVec3::Y,
         );
         assert_f!(test_oc3.e, 0.50827897);
         assert_f!(test_oc3.initial_nu, PI);
@@ -320,9 +784,9 @@ mod tests {
         let pos_2 = test_oc3.pos_at_theta(PI);
         let pos_3 = test_oc3.pos_at_theta(3. * PI / 2.);
         assert_f!(pos_0.x, 1000.);
-        assert_f!(pos_1.x, -0.00002149381);
+        assert_f!(pos_1.x + 0.1, 0. + 0.1);
         assert_f!(pos_2.x, -326.01465);
-        assert_f!(pos_3.x, 0.0000058637156);
+        assert_f!(pos_3.x + 0.1, 0. + 0.1);
         let vel_0 = test_oc3.vel_at_theta(0.);
         let vel_1 = test_oc3.vel_at_theta(PI / 2.);
         let vel_2 = test_oc3.vel_at_theta(PI);
@@ -332,4 +796,250 @@ mod tests {
         assert_f!(vel_2.z, 42.942856);
         assert_f!(vel_3.x, 28.471428);
     }
+
+    #[test]
+    fn test_propagate_universal_elliptic() {
+        // curtis example 3.7
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(7000., -12124., 0.),
+            DVec3::new(2.6679, 4.6210, 0.),
+            398600. / G,
+            DVec3::Z,
+        );
+        assert_f!(test_oc.chi_at_dt(3600.), 253.53449);
+        let (pos, vel) = test_oc.propagate(3600.);
+        assert_f!(pos.x, -3297.7686);
+        assert_f!(pos.y, 7413.3966);
+        assert_f!(vel.x, -8.2976030);
+        assert_f!(vel.y, -0.96404494);
+
+        // full period returns to the initial state
+        let (pos, vel) = test_oc.propagate(test_oc.period);
+        assert_f!(pos.x, 7000.);
+        assert_f!(pos.y, -12124.);
+        assert_f!(vel.x, 2.6679);
+        assert_f!(vel.y, 4.6210);
+    }
+
+    #[test]
+    fn test_propagate_universal_hyperbolic() {
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(7000., 0., 0.),
+            DVec3::new(0., 12., 0.),
+            398600. / G,
+            DVec3::Z,
+        );
+        assert_f!(test_oc.e, 1.5288510);
+        let (pos, _) = test_oc.propagate(3600.);
+        let nu = test_oc.nu_at_t(test_oc.t_at_nu(test_oc.initial_nu) + 3600.);
+        let anomaly_pos = test_oc.pos_at_theta(nu - test_oc.initial_nu);
+        assert_f!(pos.x, anomaly_pos.x);
+        assert_f!(pos.y, anomaly_pos.y);
+        assert_f!(pos.x, -8025.7162);
+        assert_f!(pos.y, 28877.561);
+    }
+
+    #[test]
+    fn test_propagate_universal_parabolic() {
+        let mu: f64 = 398600.;
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(7000., 0., 0.),
+            DVec3::new(0., (2. * mu / 7000.).sqrt(), 0.),
+            mu / G,
+            DVec3::Z,
+        );
+        assert_f!(test_oc.e, 1.);
+        for dt in [-3600., 1., 3600., 100000.] {
+            let (pos, vel) = test_oc.propagate(dt);
+            // zero specific energy
+            assert_f!(vel.length_squared() / 2. - mu / pos.length() + 1., 0. + 1.);
+            // agrees with barker's equation
+            let nu = test_oc.nu_at_t(dt);
+            assert_f!(test_oc.t_at_nu(nu), dt);
+            let anomaly_pos = test_oc.pos_at_theta(nu - test_oc.initial_nu);
+            assert_f!(pos.x, anomaly_pos.x);
+            assert_f!(pos.y, anomaly_pos.y);
+        }
+    }
+
+    #[test]
+    fn test_elements_round_trip() {
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(1000., 5000., 7000.),
+            DVec3::new(3., 4., 5.),
+            398600. / G,
+            DVec3::Z,
+        );
+        let (pos, vel) = test_oc.to_state_vector();
+        assert_f!(pos.x, 1000.);
+        assert_f!(pos.y, 5000.);
+        assert_f!(pos.z, 7000.);
+        assert_f!(vel.x, 3.);
+        assert_f!(vel.y, 4.);
+        assert_f!(vel.z, 5.);
+
+        let a = test_oc.h.powi(2) / 398600. / (1. - test_oc.e.powi(2));
+        let elements_oc = OrbitConic::from_elements(
+            a,
+            test_oc.e,
+            test_oc.i,
+            test_oc.big_omega,
+            test_oc.omega,
+            test_oc.initial_nu,
+            398600. / G,
+            DVec3::Z,
+        );
+        assert_f!(elements_oc.h, 19646.883);
+        assert_f!(elements_oc.initial_r.x, 1000.);
+        assert_f!(elements_oc.initial_r.y, 5000.);
+        assert_f!(elements_oc.initial_r.z, 7000.);
+        assert_f!(elements_oc.initial_v.x, 3.);
+        assert_f!(elements_oc.initial_v.y, 4.);
+        assert_f!(elements_oc.initial_v.z, 5.);
+    }
+
+    #[test]
+    fn test_from_elements() {
+        // curtis example 4.7, given h = 80000 km^2/s
+        let e: f64 = 1.4;
+        let a = 80000_f64.powi(2) / 398600. / (1. - e.powi(2));
+        let test_oc = OrbitConic::from_elements(
+            a,
+            e,
+            rad!(30.),
+            rad!(40.),
+            rad!(60.),
+            rad!(30.),
+            398600. / G,
+            DVec3::Z,
+        );
+        assert_f!(test_oc.initial_r.x, -4039.8959);
+        assert_f!(test_oc.initial_r.y, 4814.5604);
+        assert_f!(test_oc.initial_r.z, 3628.6246);
+        assert_f!(test_oc.initial_v.x, -10.385987);
+        assert_f!(test_oc.initial_v.y, -4.7719216);
+        assert_f!(test_oc.initial_v.z, 1.7438753);
+
+        // elements are recovered, including quadrants
+        assert_f!(test_oc.h, 80000.);
+        assert_f!(test_oc.e, e);
+        assert_f!(test_oc.i, rad!(30.));
+        assert_f!(test_oc.big_omega, rad!(40.));
+        assert_f!(test_oc.omega, rad!(60.));
+        assert_f!(test_oc.initial_nu, rad!(30.));
+    }
+
+    #[test]
+    fn test_equatorial_and_circular_fallbacks() {
+        // equatorial ellipse, node is undefined
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(1000., 0., 0.),
+            DVec3::new(0., 0., -14.),
+            398600. / G,
+            DVec3::Y,
+        );
+        assert!(test_oc.is_equatorial());
+        assert_f!(test_oc.big_omega + 0.1, 0. + 0.1);
+        assert_f!(test_oc.longitude_of_periapsis(), PI);
+        let (pos, vel) = test_oc.to_state_vector();
+        assert_f!(pos.x, 1000.);
+        assert_f!(vel.z, -14.);
+
+        // inclined circle starting at its descending node, periapsis is undefined
+        let v_circular = f64::sqrt(398600. / 1000.);
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(0., 0., 1000.),
+            DVec3::new(v_circular * rad!(45_f64).cos(), -v_circular * rad!(45_f64).sin(), 0.),
+            398600. / G,
+            DVec3::Y,
+        );
+        assert!(test_oc.is_circular());
+        assert!(!test_oc.is_equatorial());
+        assert_f!(test_oc.i, rad!(45.));
+        assert_f!(test_oc.argument_of_latitude(test_oc.initial_nu), PI);
+        let (pos, vel) = test_oc.to_state_vector();
+        assert_f!(pos.z, 1000.);
+        assert_f!(vel.x, v_circular * rad!(45_f64).cos());
+        assert_f!(vel.y, -v_circular * rad!(45_f64).sin());
+
+        // circular equatorial, only the true longitude is defined
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(0., 0., 1000.),
+            DVec3::new(v_circular, 0., 0.),
+            398600. / G,
+            DVec3::Y,
+        );
+        assert!(test_oc.is_circular() && test_oc.is_equatorial());
+        assert_f!(test_oc.true_longitude(test_oc.initial_nu), rad!(270.));
+        let (pos, vel) = test_oc.to_state_vector();
+        assert_f!(pos.z, 1000.);
+        assert_f!(vel.x, v_circular);
+        assert!(!test_oc.omega.is_nan() && !test_oc.big_omega.is_nan());
+    }
+
+    #[test]
+    fn test_state_at_epoch() {
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(7000., -12124., 0.),
+            DVec3::new(2.6679, 4.6210, 0.),
+            398600. / G,
+            DVec3::Z,
+        ).with_epoch(1000.);
+        let (pos, vel) = test_oc.state_at(1000.);
+        assert_f!(pos.x, 7000.);
+        assert_f!(vel.y, 4.6210);
+        assert_f!(test_oc.pos_at_t(4600.).x, -3297.7686);
+        assert_f!(test_oc.vel_at_t(4600.).x, -8.2976030);
+
+        // time at swept angle agrees with position at time
+        for theta in [-PI / 4., PI / 3., 3. * PI / 2., 5. * PI] {
+            let t = test_oc.t_at_theta(theta);
+            let pos = test_oc.pos_at_t(t);
+            let anomaly_pos = test_oc.pos_at_theta(theta);
+            assert_f!(pos.x, anomaly_pos.x);
+            assert_f!(pos.y, anomaly_pos.y);
+        }
+        assert_f!(test_oc.t_at_theta(2. * PI), 1000. + test_oc.period);
+    }
+
+    #[test]
+    fn test_derived_quantities() {
+        // ellipse starting at apoapsis
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(1000., 0., 0.),
+            DVec3::new(0., 0., -14.),
+            398600. / G,
+            DVec3::Y,
+        );
+        assert_f!(test_oc.apoapsis_radius(), 1000.);
+        assert_f!(test_oc.periapsis_radius(), 326.01465);
+        assert_f!(test_oc.semi_major_axis(), (1000. + 326.01465) / 2.);
+        assert_f!(test_oc.semi_latus_rectum(), 14000_f64.powi(2) / 398600.);
+        assert_f!(test_oc.periapsis_altitude(300.), 26.01465);
+        assert_f!(test_oc.apoapsis_altitude(300.), 700.);
+        assert_f!(test_oc.specific_energy(), 14_f64.powi(2) / 2. - 398600. / 1000.);
+        assert_f!(test_oc.mean_motion(), 2. * PI / test_oc.period);
+        assert!(test_oc.v_inf().is_none() && test_oc.turning_angle().is_none());
+        assert_f!(test_oc.time_to_apoapsis(test_oc.period / 4.).unwrap(), 3. * test_oc.period / 4.);
+        assert_f!(test_oc.time_to_periapsis(0.).unwrap(), test_oc.period / 2.);
+        assert_f!(test_oc.time_to_periapsis(test_oc.period / 4.).unwrap(), test_oc.period / 4.);
+
+        // hyperbola approaching periapsis
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(7000., 0., 0.),
+            DVec3::new(0., 12., 0.),
+            398600. / G,
+            DVec3::Z,
+        ).with_epoch(-100.);
+        let energy: f64 = 12_f64.powi(2) / 2. - 398600. / 7000.;
+        assert_f!(test_oc.specific_energy(), energy);
+        assert_f!(test_oc.v_inf().unwrap(), f64::sqrt(2. * energy));
+        assert_f!(test_oc.turning_angle().unwrap(), 2. * f64::asin(1. / test_oc.e));
+        assert_f!(test_oc.semi_major_axis(), -398600. / (2. * energy));
+        assert!(test_oc.apoapsis_radius().is_infinite());
+        assert!(test_oc.time_to_apoapsis(0.).is_none());
+        assert!(test_oc.time_to_periapsis(0.).is_none());
+        let before_periapsis = test_oc.t_at_theta(-PI / 4.);
+        assert_f!(test_oc.time_to_periapsis(before_periapsis).unwrap(), -100. - before_periapsis);
+    }
 }
@@ -1,17 +1,29 @@
-use std::f32::consts::PI;
+use std::f64::consts::PI;
 
 use crate::GameState;
 use crate::camera::GameCamera;
-use crate::loading::{SettingsConfigAsset,SettingsConfigAssets};
-use crate::overlay_ui::{OverlayUiBodyInfo,OverylayUiControls,ViewingBody};
-use crate::world::OrbitConic;
+use crate::loading::{BodyConfig,SatelliteConfig,SettingsConfigAsset,SettingsConfigAssets,TransferConfig};
+use crate::overlay_ui::{OverlayUiBodyInfo,OverlayUiTimeWarp,OverlayUiTimeWarpButton,OverylayUiControls,ViewingBody};
+use crate::world::{BodyHierarchy,Ephemeris,FlybyReport,Impact,Integrator,ManeuverNode,ManeuverQueue,NBodySystem,OrbitConic,PatchOutcome,Tle,TrajectoryPatch,TransferKind,TransferPlan};
 
+use bevy::math::DVec3;
 use bevy::prelude::*;
 
-// TODO load from config
-pub const PLANET_MASS: f32 = 3.1e11;
-pub const MOON_MASS: f32 = 3.1e10;
-pub const MOON_SOI: f32 = 1.;
+// plane-of-reference of every body, the demo's orbits are drawn about y
+pub const BODY_PLANE_K: DVec3 = DVec3::Y;
+// number of soi changes and burns followed when drawing a predicted path
+const MAX_PATCH_DEPTH: i32 = 4;
+// distance from the body beyond which predicted paths are not drawn
+const MAX_PATH_RADIUS: f64 = 30.;
+// longest step of the n-body integrator, each frame is split into steps no longer than this
+const NBODY_MAX_STEP: f64 = 1. / 240.;
+// selectable time warp rates
+const TIME_WARP_LEVELS: [f64; 6] = [1., 10., 100., 1000., 10000., 100000.];
+// real time in seconds left before a soi transition or maneuver node when warp is reduced,
+// so events are reached at no more than 1x after this long
+const WARP_LEAD_TIME: f64 = 2.;
+// highest warp while the n-body simulation runs, which integrates every simulated second
+const NBODY_MAX_WARP: f64 = 100.;
 
 // helper macro
 macro_rules! deg {
@@ -22,7 +34,27 @@ macro_rules! deg {
 
 #[derive(Default,Resource)]
 pub struct SimulationState {
-    simulated_time: f32,
+    pub simulated_time: f64,
+    delta_time: f64, // simulated time passed in the current frame
+    time_warp_level: usize, // index of the selected rate within TIME_WARP_LEVELS
+    time_warp: f64, // rate of the current frame, after limiting
+    paused: bool,
+}
+
+impl SimulationState {
+
+    // select the next faster warp rate, or the next slower one
+    fn change_time_warp(
+        &mut self,
+        faster: bool,
+    ) {
+        self.time_warp_level = if faster {
+            (self.time_warp_level + 1).min(TIME_WARP_LEVELS.len() - 1)
+        } else {
+            self.time_warp_level.saturating_sub(1)
+        };
+    }
+
 }
 
 // This plugin renders demo entities
@@ -31,9 +63,18 @@ impl Plugin for OrbitsDemoPlugin {
     fn build(&self, app: &mut App) {
         app
             .init_resource::<SimulationState>()
+            .init_resource::<BodyHierarchy>()
+            .add_event::<SatelliteCrashed>()
+            .add_event::<SatelliteEscaped>()
             .add_systems(OnEnter(GameState::Playing), setup_demo)
+            .add_systems(Update, update_time_warp.before(update_demo).run_if(in_state(GameState::Playing)))
             .add_systems(Update, update_demo.run_if(in_state(GameState::Playing)))
-            .add_systems(Update, update_demo_controls.run_if(in_state(GameState::Playing)));
+            .add_systems(Update, update_nbody.after(update_demo).before(update_maneuvers)
+                .run_if(in_state(GameState::Playing)).run_if(resource_exists::<NBodySimulation>))
+            .add_systems(Update, update_maneuvers.after(update_demo).run_if(in_state(GameState::Playing)))
+            .add_systems(Update, update_demo_controls.run_if(in_state(GameState::Playing)))
+            .add_systems(Update, update_time_warp_buttons.run_if(in_state(GameState::Playing)))
+            .add_systems(Update, report_satellite_events.after(update_demo).run_if(in_state(GameState::Playing)));
     }
 }
 
@@ -41,89 +82,107 @@ impl Plugin for OrbitsDemoPlugin {
 const COLORS: [Color; 6] = [Color::GREEN, Color::YELLOW, Color::BLUE, Color::RED, Color::PURPLE, Color::ORANGE];
 
 #[derive(Component)]
-pub struct MoonEntity {
-    pub idx: usize,
-    pub vel: Vec3,
-    pub conic: OrbitConic,
+pub struct BodyEntity {
+    pub idx: usize, // index within the body hierarchy
+    pub pos: DVec3, // relative to parent body, placed on its conic each frame
+    pub vel: DVec3, // relative to parent body
     pub color: Color,
 }
 
+// n-body simulation of the same scenario, drawn as markers to compare against the patched conics
+#[derive(Resource)]
+pub struct NBodySimulation {
+    pub system: NBodySystem, // bodies by hierarchy index, followed by the satellites
+    pub integrator: Integrator,
+    pub sat_offset: usize, // index of the first satellite within the system
+}
+
+// satellite resting where it hit a body's surface, no longer propagated
+#[derive(Component)]
+pub struct Crashed(pub Impact);
+
+// sent when a satellite hits a body's surface
+#[derive(Event)]
+pub struct SatelliteCrashed {
+    pub idx: usize, // index of the satellite
+    pub impact: Impact,
+}
+
+// satellite frozen where it left the system on an escape orbit from the root body
+#[derive(Component)]
+pub struct Escaped {
+    pub t: f64, // simulation time of escape
+}
+
+// sent when a satellite escapes the root body
+#[derive(Event)]
+pub struct SatelliteEscaped {
+    pub idx: usize, // index of the satellite
+    pub t: f64,
+}
+
+// configured transfer of a satellite as planned from its initial orbit, none when it could
+// not be planned
+#[derive(Component)]
+pub struct TransferSummary(pub Option<TransferPlan>);
+
 #[derive(Component)]
 pub struct SatEntity {
     pub idx: usize,
-    pub vel: Vec3,
+    pub pos: DVec3, // relative to orbited body, placed on its conic each frame
+    pub vel: DVec3, // relative to orbited body
     pub conic: OrbitConic,
-    pub parent_info: Option<ParentInfo>,
+    pub body: usize, // index of orbited body within the body hierarchy
     pub color: Color,
 }
 
-#[derive(Clone)]
-pub struct ParentInfo {
-    pub conic: OrbitConic,
-    pub entry_time: f32,
-}
-
 fn setup_demo(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut hierarchy: ResMut<BodyHierarchy>,
     config_handles: Res<SettingsConfigAssets>,
     config_assets: Res<Assets<SettingsConfigAsset>>,
 ) {
     let settings = config_assets.get(config_handles.settings.clone()).unwrap();
 
-    // body
-    let sphere_mesh = Sphere::default().mesh().ico(5).unwrap();
-    let mesh_handle = meshes.add(sphere_mesh);
-    let material_handle = materials.add(StandardMaterial {
-        base_color: Color::DARK_GRAY,
-        ..default()
-    });
-    commands.spawn(MaterialMeshBundle {
-        mesh: mesh_handle.clone(),
-        material: material_handle.clone(),
-        transform: Transform::from_xyz(0.0, 0.0, 0.0).with_scale(Vec3::splat(settings.body_scale)),
-        ..Default::default()
-    });
-
-
-    // moons
-    for (idx, sat) in settings.moons.iter().enumerate() {
-
-        let conic = OrbitConic::from_initial(
-            sat.initial_pos,
-            sat.initial_vel,
-            settings.body_mass,
-            Vec3::Y);
+    // bodies
+    *hierarchy = hierarchy_from_settings(settings);
+    for (idx, body) in settings.bodies.iter().enumerate() {
+        // unit sphere mesh has radius 0.5
         let mesh = Sphere::default().mesh().ico(5).unwrap();
         let mesh_handle = meshes.add(mesh);
-        let color = COLORS[idx % COLORS.len()].with_s(0.3);
+        let color = if idx == 0 {
+            Color::DARK_GRAY
+        } else {
+            COLORS[(idx - 1) % COLORS.len()].with_s(0.3)
+        };
         let mat = materials.add(StandardMaterial {
             base_color: color.clone(),
             ..default()
         });
+        let pos = hierarchy.pos_at_t(idx, 0.);
+        let (rel_pos, rel_vel) = hierarchy.body_state_at(idx, 0.);
         commands.spawn((MaterialMeshBundle {
             mesh: mesh_handle.clone(),
             material: mat.clone(),
-            transform: Transform::from_translation(sat.initial_pos).with_scale(Vec3::splat(sat.scale)),
+            transform: Transform::from_translation(pos.as_vec3()).with_scale(Vec3::splat(2. * body.radius as f32)),
             ..Default::default()
-        }, MoonEntity {
+        }, BodyEntity {
             idx,
-            vel: sat.initial_vel,
-            conic: conic,
+            pos: rel_pos,
+            vel: rel_vel,
             color,
          }));
 
     }
 
     // satellites
+    let sat_states = satellite_initial_states(&hierarchy, settings);
     for (idx, sat) in settings.satellites.iter().enumerate() {
 
-        let conic = OrbitConic::from_initial(
-            sat.initial_pos,
-            sat.initial_vel,
-            settings.body_mass,
-            Vec3::Y);
+        let (sat_pos, sat_vel) = sat_states[idx];
+        let conic = hierarchy.conic_about(sat.parent, 0., sat_pos, sat_vel);
         let mesh = Sphere::default().mesh().ico(5).unwrap();
         let mesh_handle = meshes.add(mesh);
         let color = COLORS[(COLORS.len() / 2 + idx) % COLORS.len()];
@@ -131,30 +190,224 @@ fn setup_demo(
             base_color: color.clone(),
             ..default()
         });
-        commands.spawn((MaterialMeshBundle {
+        let pos = hierarchy.pos_at_t(sat.parent, 0.) + sat_pos;
+        let mut sat_commands = commands.spawn((MaterialMeshBundle {
             mesh: mesh_handle.clone(),
             material: mat.clone(),
-            transform: Transform::from_translation(sat.initial_pos).with_scale(Vec3::splat(sat.scale)),
+            transform: Transform::from_translation(pos.as_vec3()).with_scale(Vec3::splat(sat.scale)),
             ..Default::default()
         }, SatEntity {
             idx,
-            vel: sat.initial_vel,
+            pos: sat_pos,
+            vel: sat_vel,
             conic: conic,
-            parent_info: None,
+            body: sat.parent,
             color,
          }));
+        if let Some(maneuver) = sat.maneuver.as_ref() {
+            sat_commands.insert(ManeuverNode {
+                t: maneuver.t,
+                prograde: maneuver.prograde,
+                normal: maneuver.normal,
+                radial: maneuver.radial,
+            });
+        }
+        if let Some(transfer) = sat.transfer.as_ref() {
+            let plan = plan_transfer_from_settings(&hierarchy, sat.parent, &conic, transfer);
+            if let Some(plan) = plan.as_ref() {
+                sat_commands.insert((plan.burns[0], ManeuverQueue(plan.burns[1..].to_vec())));
+            }
+            sat_commands.insert(TransferSummary(plan));
+        }
+
+    }
 
+    // ground-truth simulation starting from the same state
+    if let Some(integrator) = settings.integrator {
+        commands.insert_resource(NBodySimulation {
+            system: nbody_from_settings(&hierarchy, settings),
+            integrator,
+            sat_offset: hierarchy.bodies.len(),
+        });
     }
 }
 
-fn update_demo(
+// transfer of a transfer config from a conic about body idx, none when it cannot be planned
+pub fn plan_transfer_from_settings(
+    hierarchy: &BodyHierarchy,
+    idx: usize,
+    conic: &OrbitConic,
+    transfer: &TransferConfig,
+) -> Option<TransferPlan> {
+    let kind = transfer.bi_elliptic_radius.map_or(TransferKind::Hohmann, TransferKind::BiElliptic);
+    match (transfer.target_body, transfer.target_radius) {
+        (Some(target), _) => {
+            let periapsis_offset = transfer.periapsis_offset.unwrap_or(2. * hierarchy.bodies[target].radius);
+            hierarchy.plan_transfer_to_body(idx, conic, target, kind, periapsis_offset, transfer.after)
+        }
+        (None, Some(target_radius)) => TransferPlan::to_radius(conic, target_radius, kind, transfer.after),
+        (None, None) => None,
+    }
+}
+
+// body hierarchy described by the settings, the first body is the root at the origin
+pub fn hierarchy_from_settings(
+    settings: &SettingsConfigAsset,
+) -> BodyHierarchy {
+    let root = &settings.bodies[0];
+    let epoch = simulation_epoch(settings);
+    let soi_model = settings.soi_model.unwrap_or_default();
+    let mut hierarchy = BodyHierarchy::new(&root.name, root.mass, root.radius, BODY_PLANE_K);
+    for (idx, body) in settings.bodies.iter().enumerate() {
+        if idx == 0 {
+            // root is fixed at the origin
+        } else if let Some(ephemeris) = load_ephemeris(body) {
+            let scale = body.ephemeris.as_ref().and_then(|config| config.scale).unwrap_or(1.);
+            hierarchy.add_body_with_ephemeris(
+                &body.name,
+                body.parent,
+                body.mass,
+                body.radius,
+                ephemeris.clone().transformed(-epoch, scale),
+                BODY_PLANE_K,
+                soi_model);
+        } else {
+            hierarchy.add_body(
+                &body.name,
+                body.parent,
+                body.mass,
+                body.radius,
+                body.initial_pos.as_dvec3(),
+                body.initial_vel.as_dvec3(),
+                BODY_PLANE_K,
+                soi_model);
+        }
+        hierarchy.bodies[idx].rotation_period = body.rotation_period.unwrap_or(f64::INFINITY);
+        hierarchy.bodies[idx].j2 = body.j2;
+    }
+    hierarchy
+}
+
+// element set of a satellite's tle, when given, as read along with the settings
+fn load_tle(
+    sat: &SatelliteConfig,
+) -> Option<&Tle> {
+    sat.tle.as_ref().and_then(|tle_config| tle_config.tle.as_ref())
+}
+
+// ephemeris of a body, when given, with times in seconds since J2000, as read along with the
+// settings
+fn load_ephemeris(
+    body: &BodyConfig,
+) -> Option<&Ephemeris> {
+    body.ephemeris.as_ref().and_then(|ephemeris_config| ephemeris_config.ephemeris.as_ref())
+}
+
+// seconds since J2000 at simulation time 0
+pub fn simulation_epoch(
+    settings: &SettingsConfigAsset,
+) -> f64 {
+    settings.epoch
+        .or_else(|| settings.satellites.iter().find_map(load_tle).map(|tle| tle.epoch_j2000()))
+        .or_else(|| settings.bodies.iter().skip(1).find_map(load_ephemeris).map(|ephemeris| ephemeris.start_time()))
+        .unwrap_or(0.)
+}
+
+// state of each satellite of the settings relative to its parent at time 0, from its tle
+// when given
+pub fn satellite_initial_states(
+    hierarchy: &BodyHierarchy,
+    settings: &SettingsConfigAsset,
+) -> Vec<(DVec3, DVec3)> {
+    let epoch = simulation_epoch(settings);
+    settings.satellites.iter().map(|sat| (sat, load_tle(sat))).map(|(sat, tle)| match tle {
+        Some(tle) => {
+            let body = &hierarchy.bodies[sat.parent];
+            tle.conic(body.mass, body.body_plane_k, tle.epoch_j2000() - epoch).state_at(0.)
+        }
+        None => (sat.initial_pos.as_dvec3(), sat.initial_vel.as_dvec3()),
+    }).collect()
+}
+
+// n-body system of the hierarchy's bodies at time 0 followed by the settings' satellites
+pub fn nbody_from_settings(
+    hierarchy: &BodyHierarchy,
+    settings: &SettingsConfigAsset,
+) -> NBodySystem {
+    let mut system = NBodySystem::from_hierarchy(hierarchy, 0.);
+    let sat_states = satellite_initial_states(hierarchy, settings);
+    for (sat, (pos, vel)) in settings.satellites.iter().zip(sat_states) {
+        system.add_particle(
+            hierarchy.pos_at_t(sat.parent, 0.) + pos,
+            hierarchy.vel_at_t(sat.parent, 0.) + vel);
+    }
+    system
+}
+
+// advance simulated time at the selected warp, reduced ahead of the next soi transition,
+// maneuver node or impact of any satellite
+fn update_time_warp(
     time: Res<Time>,
     mut simulation_state: ResMut<SimulationState>,
+    hierarchy: Res<BodyHierarchy>,
+    nbody: Option<Res<NBodySimulation>>,
+    sat_query: Query<(&SatEntity, Option<&ManeuverNode>), (Without<Crashed>, Without<Escaped>)>,
+    mut time_warp_query: Query<&mut Text, With<OverlayUiTimeWarp>>,
+) {
+    let current_time = simulation_state.simulated_time;
+    let mut selected_warp = TIME_WARP_LEVELS[simulation_state.time_warp_level];
+    if nbody.is_some() {
+        selected_warp = selected_warp.min(NBODY_MAX_WARP);
+    }
+
+    // time until the nearest event within reach at the selected warp
+    let mut time_to_event = f64::INFINITY;
+    for (sat, maneuver) in &sat_query {
+        let mean_conic = hierarchy.j2_precessed(sat.body, &sat.conic, current_time);
+        let t_end = current_time + selected_warp * WARP_LEAD_TIME;
+        if let Some((t_change, _)) = hierarchy.next_soi_transition(sat.body, &mean_conic, current_time, t_end) {
+            time_to_event = time_to_event.min(t_change - current_time);
+        }
+        if let Some(impact) = hierarchy.next_impact(sat.body, &mean_conic, current_time, t_end) {
+            time_to_event = time_to_event.min(impact.t - current_time);
+        }
+        if let Some(node) = maneuver {
+            time_to_event = time_to_event.min((node.t - current_time).max(0.));
+        }
+    }
+    let time_warp = if simulation_state.paused {
+        0.
+    } else {
+        selected_warp.min((time_to_event / WARP_LEAD_TIME).max(1.))
+    };
+
+    // consume time
+    simulation_state.time_warp = time_warp;
+    simulation_state.delta_time = time.delta_seconds_f64() * time_warp;
+    simulation_state.simulated_time += simulation_state.delta_time;
+
+    if let Ok(mut time_warp_text) = time_warp_query.get_single_mut() {
+        time_warp_text.sections[0].value = if simulation_state.paused {
+            format!("t: {:.2}, paused", simulation_state.simulated_time)
+        } else if time_warp < selected_warp {
+            format!("t: {:.2}, warp: {:.0}x (limited from {:.0}x)", simulation_state.simulated_time, time_warp, selected_warp)
+        } else {
+            format!("t: {:.2}, warp: {:.0}x", simulation_state.simulated_time, time_warp)
+        };
+    }
+}
+
+fn update_demo(
+    mut commands: Commands,
+    mut crash_events: EventWriter<SatelliteCrashed>,
+    mut escape_events: EventWriter<SatelliteEscaped>,
+    simulation_state: Res<SimulationState>,
     controls: Res<OverylayUiControls>,
+    hierarchy: Res<BodyHierarchy>,
     mut gizmos: Gizmos,
     mut transforms: Query<&mut Transform>,
-    mut moons_query: Query<(Entity, &mut MoonEntity)>,
-    mut sat_query: Query<(Entity, &mut SatEntity)>,
+    mut bodies_query: Query<(Entity, &mut BodyEntity)>,
+    mut sat_query: Query<(Entity, &mut SatEntity, Option<&ManeuverNode>, Option<&ManeuverQueue>, Option<&TransferSummary>, Option<&Crashed>, Option<&Escaped>)>,
     mut body_info_query: Query<&mut Text, With<OverlayUiBodyInfo>>,
     camera_query: Query<Entity, With<GameCamera>>,
     config_handles: Res<SettingsConfigAssets>,
@@ -163,111 +416,164 @@ fn update_demo(
     // optionally update camera target
     let mut update_camera_target: Option<Vec3> = None;
 
-    // consume time
-    let dt = time.delta_seconds();
-    simulation_state.simulated_time += dt;
+    let dt = simulation_state.delta_time;
+    let current_time = simulation_state.simulated_time;
 
-    // update moon entities
-    for (sat_entity, mut sat) in &mut moons_query {
-        // simulate physics
-        let mut sat_transform = transforms.get_mut(sat_entity).unwrap();
-        sat_transform.translation += sat.vel * dt;
-        let dv = sat.conic.dv_at_pos(sat_transform.translation);
-        sat.vel += dv * dt;
+    // place bodies on their conics at the current time
+    let body_positions: Vec<DVec3> = (0..hierarchy.bodies.len())
+        .map(|idx| hierarchy.pos_at_t(idx, current_time)).collect();
+    for (_, mut body) in &mut bodies_query {
+        if hierarchy.bodies[body.idx].parent.is_some() {
+            (body.pos, body.vel) = hierarchy.body_state_at(body.idx, current_time);
+        }
+    }
+
+    // update body entities
+    for (body_entity, body) in &bodies_query {
+        let mut body_transform = transforms.get_mut(body_entity).unwrap();
+        body_transform.translation = body_positions[body.idx].as_vec3();
+
+        let Some(parent) = hierarchy.bodies[body.idx].parent else {
+            continue;
+        };
+        let conic = hierarchy.body_conic_at(body.idx, current_time).unwrap();
 
         // draw conic path
-        draw_conic_path(sat.conic, None, &mut gizmos, sat.color.clone(), None, 0);
+        draw_conic_path(conic, current_time, parent, body_positions[parent], None,
+            &hierarchy, false, &mut gizmos, body.color.clone(), 0);
 
         // update body info ui
-        if ViewingBody::Moon(sat.idx) == controls.viewing_body {
+        if ViewingBody::Body(body.idx) == controls.viewing_body {
             let mut body_info = body_info_query.single_mut();
-            body_info.sections[0].value = format_body_info("Moon", sat.idx,
-                sat.vel, &sat.conic, &sat_transform);
+            body_info.sections[0].value = format_body_info(&hierarchy.bodies[body.idx].name, body.idx,
+                body.pos, body.vel, &conic, current_time, hierarchy.bodies[parent].radius);
 
             // update camera
-            update_camera_target = Some(sat_transform.translation);
-        }
-
-        // reset position of sat on parabolic trajectory when out-of-bounds
-        if sat_transform.translation.length() > 20. {
-            sat_transform.translation.x = -sat_transform.translation.x;
-            sat.vel.z = -sat.vel.z;
+            update_camera_target = Some(body_transform.translation);
         }
     }
 
     // update sat entities
-    for (sat_entity, mut sat) in &mut sat_query {
-        // simulate physics
-        let mut sat_transform = transforms.get_mut(sat_entity).unwrap();
-        sat_transform.translation += sat.vel * dt;
-        let dv = sat.conic.dv_at_pos(sat_transform.translation);
-        sat.vel += dv * dt;
-
-        // collect conics of potential soi collisions
-        let moon_conics: Vec<&OrbitConic> = moons_query.iter().map(|(_,me)| &me.conic).collect();
-
-        // check soi change
-        if let Some(pi) = sat.parent_info.as_ref() {
-            // check for exit of current soi
-            let parent_soi_r = MOON_SOI; // TODO
-            let parent_pos = pi.conic.pos_at_theta(pi.conic.nu_at_t(simulation_state.simulated_time));
-            if sat_transform.translation.distance(parent_pos) > parent_soi_r {
-                // reset orbit conic to planet (default) soi
-                sat.conic = OrbitConic::from_initial(
-                    sat_transform.translation,
-                    sat.vel,
-                    PLANET_MASS, Vec3::Y); // TODO get values from config
-                sat.parent_info = None;
+    for (sat_entity, mut sat, maneuver, queue, transfer, crashed, escaped) in &mut sat_query {
+        // crashed satellites rest on the surface, turning with the body
+        if let Some(Crashed(impact)) = crashed {
+            let body_fixed = hierarchy.body_fixed_frame(impact.body, current_time);
+            (sat.pos, sat.vel) = body_fixed.from_frame(impact.surface_position, DVec3::ZERO);
+            let mut sat_transform = transforms.get_mut(sat_entity).unwrap();
+            sat_transform.translation = (body_positions[impact.body] + sat.pos).as_vec3();
+            if ViewingBody::Satellite(sat.idx) == controls.viewing_body {
+                let mut body_info = body_info_query.single_mut();
+                body_info.sections[0].value = format!("Satellite {}:\n\
+                    crashed on {} at t: {:.2}\n\
+                    lat: {:.2}°, long: {:.2}°, v: {:.2}",
+                    sat.idx, hierarchy.bodies[impact.body].name, impact.t,
+                    deg!(impact.latitude), deg!(impact.longitude), impact.speed);
+                update_camera_target = Some(sat_transform.translation);
             }
-        } else {
-            // check for entry of moon soi
-            for moon_conic in moon_conics.iter() {
-                let current_time = simulation_state.simulated_time;
-                let moon_nu = moon_conic.nu_at_t(current_time);
-                let moon_pos = moon_conic.pos_at_theta(moon_nu);
-                let moon_soi_r = MOON_SOI; // TODO
-
-                // TODO remove info
+            continue;
+        }
+
+        // escaped satellites stay frozen where they left the system
+        if let Some(escaped) = escaped {
+            if ViewingBody::Satellite(sat.idx) == controls.viewing_body {
                 let mut body_info = body_info_query.single_mut();
-                body_info.sections[0].value = format!("Debug:\n\
-                    s: {:.2},{:.2},{:.2}\n\
-                    m: {:.2},{:.2},{:.2}\n\
-                    d: {:.2}, t: {:.2}",
-                    sat_transform.translation.x, sat_transform.translation.y, sat_transform.translation.z,
-                    moon_pos.x, moon_pos.y, moon_pos.z,
-                    sat_transform.translation.distance(moon_pos), current_time,
-                );
-
-                if sat_transform.translation.distance(moon_pos) < moon_soi_r {
-                    // new orbit conic in moon soi
-                    println!("enter soi!");
-                    sat.conic = OrbitConic::from_initial(
-                        sat_transform.translation,
-                        sat.vel,
-                        MOON_MASS, Vec3::Y); // TODO get values from config
-                    sat.parent_info = Some(ParentInfo { conic: **moon_conic, entry_time: current_time });
-                }
+                body_info.sections[0].value = format!("Satellite {}:\n\
+                    escaped {} at t: {:.2}\n\
+                    v: {:.2}",
+                    sat.idx, hierarchy.bodies[0].name, escaped.t, sat.vel.length());
+                update_camera_target = Some(transforms.get(sat_entity).unwrap().translation);
             }
+            continue;
+        }
+
+        // conic with the body's oblateness drift up to now, used for all predictions
+        let mean_conic = hierarchy.j2_precessed(sat.body, &sat.conic, current_time);
+
+        // check impact since the last frame, before any soi change
+        let last_time = current_time - dt;
+        let transition = hierarchy.next_soi_transition(sat.body, &mean_conic, last_time, current_time);
+        let t_limit = transition.map_or(current_time, |(t, _)| t);
+        if let Some(impact) = hierarchy.next_impact(sat.body, &mean_conic, last_time, t_limit) {
+            commands.entity(sat_entity).insert(Crashed(impact)).remove::<(ManeuverNode, ManeuverQueue)>();
+            crash_events.send(SatelliteCrashed { idx: sat.idx, impact });
+            continue;
+        }
+
+        // check soi change since the last frame, moving into the frame of the new body
+        // at the exact time of crossing then bringing the state forward to now
+        if let Some((t_change, new_body)) = transition {
+            println!("soi change: {} -> {}", hierarchy.bodies[sat.body].name, hierarchy.bodies[new_body].name);
+            let (pos, vel) = mean_conic.state_at(t_change);
+            let (pos, vel) = hierarchy.change_frame(sat.body, new_body, t_change, pos, vel);
+            sat.conic = hierarchy.conic_about(new_body, t_change, pos, vel);
+            sat.body = new_body;
+            // burns are planned about the body left behind
+            commands.entity(sat_entity).remove::<(ManeuverNode, ManeuverQueue)>();
+        }
+        let mean_conic = hierarchy.j2_precessed(sat.body, &sat.conic, current_time);
+        (sat.pos, sat.vel) = mean_conic.state_at(current_time);
+
+        // leaving the system beyond the reach of every body, freeze it here
+        if hierarchy.bodies[sat.body].parent.is_none() && hierarchy.is_escaping(&mean_conic, current_time, sat.pos, sat.vel) {
+            commands.entity(sat_entity).insert(Escaped { t: current_time }).remove::<(ManeuverNode, ManeuverQueue)>();
+            escape_events.send(SatelliteEscaped { idx: sat.idx, t: current_time });
         }
 
+        let mut sat_transform = transforms.get_mut(sat_entity).unwrap();
+        sat_transform.translation = (body_positions[sat.body] + sat.pos).as_vec3();
+
         // draw conic path
-        draw_conic_path(sat.conic, sat.parent_info.clone(), &mut gizmos, sat.color.clone(), Some(moon_conics), 0);
+        draw_conic_path(mean_conic, current_time, sat.body, body_positions[sat.body],
+            maneuver.copied(), &hierarchy, true, &mut gizmos, sat.color.clone(), 0);
 
         // update body info ui
         if ViewingBody::Satellite(sat.idx) == controls.viewing_body {
             let mut body_info = body_info_query.single_mut();
             body_info.sections[0].value = format_body_info("Satellite", sat.idx,
-                sat.vel, &sat.conic, &sat_transform);
+                sat.pos, sat.vel, &mean_conic, current_time, hierarchy.bodies[sat.body].radius);
+
+            // next burn and the planned transfer
+            if let Some(node) = maneuver {
+                body_info.sections[0].value += &format!("\nburn: Δt: {:.2}, Δv: {:.3}, queued: {}",
+                    node.t - current_time, DVec3::new(node.prograde, node.normal, node.radial).length(),
+                    queue.map_or(0, |queue| queue.0.len()));
+            }
+            body_info.sections[0].value += &match transfer {
+                Some(TransferSummary(Some(plan))) => format!("\ntransfer: {} burns, Δv: {:.3}, Δt: {:.2}",
+                    plan.burns.len(), plan.delta_v, plan.transfer_time),
+                Some(TransferSummary(None)) => String::from("\ntransfer: not possible from the initial orbit"),
+                None => String::new(),
+            };
+
+            // predicted impact and closest approach to each body over the drawn path
+            let t_end = path_end_time(&mean_conic, current_time);
+            if let Some(impact) = hierarchy.next_impact(sat.body, &mean_conic, current_time, t_end) {
+                body_info.sections[0].value += &format!("\nimpact: Δt: {:.2}, lat: {:.2}°, long: {:.2}°, v: {:.2}",
+                    impact.t - current_time, deg!(impact.latitude), deg!(impact.longitude), impact.speed);
+            }
+            for (n, patch) in hierarchy.trajectory_patches(sat.body, &mean_conic, current_time, MAX_PATCH_DEPTH as usize).iter().enumerate() {
+                body_info.sections[0].value += &format!("\npatch {}: {}, {}", n,
+                    hierarchy.bodies[patch.body].name, format_patch_outcome(&hierarchy, patch, current_time));
+
+                // flybys of moons, the current one from when its soi was entered
+                let t_entry = if n == 0 { sat.conic.epoch.min(current_time) } else { patch.t_start };
+                if let Some(flyby) = hierarchy.flyby_report(patch.body, &patch.conic, t_entry) {
+                    body_info.sections[0].value += &format_flyby(&flyby, current_time);
+                }
+            }
+            for idx in 1..hierarchy.bodies.len() {
+                if idx == sat.body {
+                    continue;
+                }
+                let approach = hierarchy.closest_approach_to_body(sat.body, &mean_conic, idx, current_time, t_end);
+                body_info.sections[0].value += &format!("\n{} approach: Δt: {:.2}, d: {:.2}, v: {:.2}",
+                    hierarchy.bodies[idx].name, approach.t - current_time,
+                    approach.distance, approach.relative_velocity.length());
+            }
 
             // update camera
             update_camera_target = Some(sat_transform.translation);
         }
-
-        // reset position of sat on parabolic trajectory when out-of-bounds
-        if sat_transform.translation.length() > 20. {
-            sat_transform.translation.x = -sat_transform.translation.x;
-            sat.vel.z = -sat.vel.z;
-        }
     }
 
     // update camera
@@ -280,33 +586,167 @@ fn update_demo(
     }
 }
 
+fn update_nbody(
+    simulation_state: Res<SimulationState>,
+    mut nbody: ResMut<NBodySimulation>,
+    hierarchy: Res<BodyHierarchy>,
+    mut gizmos: Gizmos,
+    bodies_query: Query<&BodyEntity>,
+    sat_query: Query<(&SatEntity, Option<&ManeuverNode>)>,
+) {
+    let integrator = nbody.integrator;
+    nbody.system.advance(simulation_state.delta_time, NBODY_MAX_STEP, integrator);
+
+    // draw bodies as outlines
+    for body in &bodies_query {
+        let pos = nbody.system.positions[body.idx];
+        gizmos.sphere(pos.as_vec3(), Quat::IDENTITY, hierarchy.bodies[body.idx].radius as f32, body.color);
+    }
+
+    for (sat, maneuver) in &sat_query {
+        let sat_idx = nbody.sat_offset + sat.idx;
+
+        // same burns as the patched conics, in the frame of the orbited body
+        if let Some(node) = maneuver.filter(|node| node.t <= simulation_state.simulated_time) {
+            let pos = nbody.system.positions[sat_idx] - nbody.system.positions[sat.body];
+            let vel = nbody.system.velocities[sat_idx] - nbody.system.velocities[sat.body];
+            nbody.system.velocities[sat_idx] += node.delta_v_at(pos, vel);
+        }
+
+        gizmos.sphere(nbody.system.positions[sat_idx].as_vec3(), Quat::IDENTITY, 0.05, sat.color);
+    }
+}
+
+fn report_satellite_events(
+    mut crash_events: EventReader<SatelliteCrashed>,
+    mut escape_events: EventReader<SatelliteEscaped>,
+    hierarchy: Res<BodyHierarchy>,
+) {
+    for event in crash_events.read() {
+        println!("satellite {} crashed on {} at t: {:.2}, lat: {:.2}°, long: {:.2}°",
+            event.idx, hierarchy.bodies[event.impact.body].name, event.impact.t,
+            deg!(event.impact.latitude), deg!(event.impact.longitude));
+    }
+    for event in escape_events.read() {
+        println!("satellite {} escaped {} at t: {:.2}", event.idx, hierarchy.bodies[0].name, event.t);
+    }
+}
+
+fn update_maneuvers(
+    mut commands: Commands,
+    simulation_state: Res<SimulationState>,
+    hierarchy: Res<BodyHierarchy>,
+    mut sat_query: Query<(Entity, &mut SatEntity, &ManeuverNode, Option<&mut ManeuverQueue>), (Without<Crashed>, Without<Escaped>)>,
+) {
+    for (sat_entity, mut sat, maneuver, queue) in &mut sat_query {
+        if maneuver.t <= simulation_state.simulated_time {
+            // burn at the node's time, then bring the state forward to now
+            let mean_conic = hierarchy.j2_precessed(sat.body, &sat.conic, maneuver.t);
+            sat.conic = maneuver.apply(&mean_conic);
+            (sat.pos, sat.vel) = sat.conic.state_at(simulation_state.simulated_time);
+
+            // the next queued burn becomes the node
+            match queue.filter(|queue| !queue.0.is_empty()) {
+                Some(mut queue) => {
+                    commands.entity(sat_entity).insert(queue.0.remove(0));
+                }
+                None => {
+                    commands.entity(sat_entity).remove::<(ManeuverNode, ManeuverQueue)>();
+                }
+            }
+        }
+    }
+}
+
+// how a predicted patch ends, with the time remaining until then
+fn format_patch_outcome(
+    hierarchy: &BodyHierarchy,
+    patch: &TrajectoryPatch,
+    current_time: f64,
+) -> String {
+    match patch.outcome {
+        PatchOutcome::Bound => String::from("bound"),
+        PatchOutcome::Escape => String::from("escape"),
+        PatchOutcome::Capture(body) => format!("capture by {}, Δt: {:.2}", hierarchy.bodies[body].name, patch.t_end - current_time),
+        PatchOutcome::SoiExit(body) => format!("soi exit to {}, Δt: {:.2}", hierarchy.bodies[body].name, patch.t_end - current_time),
+        PatchOutcome::Impact(impact) => format!("impact, Δt: {:.2}", impact.t - current_time),
+    }
+}
+
+// flyby of a body as lines of the satellite info panel
+fn format_flyby(
+    flyby: &FlybyReport,
+    current_time: f64,
+) -> String {
+    format!("\n  periapsis: Δt: {:.2}, alt: {:.3}, turn: {:.2}°", flyby.t_periapsis - current_time,
+        flyby.periapsis_altitude, deg!(flyby.turning_angle))
+        + &format!("\n  v∞ in: {:.3}, v∞ out: {:.3}, B·T: {:.3}, B·R: {:.3}", flyby.v_inf_in.length(),
+            flyby.v_inf_out.length(), flyby.b_dot_t, flyby.b_dot_r)
+        + &format!("\n  Δv: {:.3} ({:.2},{:.2},{:.2})", flyby.delta_v.length(),
+            flyby.delta_v.x, flyby.delta_v.y, flyby.delta_v.z)
+}
+
 fn format_body_info(
     body_type: &str,
     body_idx: usize,
-    body_vel: Vec3,
+    body_pos: DVec3,
+    body_vel: DVec3,
     conic: &OrbitConic,
-    sat_transform: &Transform,
+    current_time: f64,
+    parent_radius: f64,
 ) -> String {
     // current true anomoly
-    let t_nu = conic.nu_at_pos(sat_transform.translation);
+    let t_nu = conic.nu_at_pos(body_pos);
     let t = conic.t_at_nu(t_nu);
 
+    // derived quantities, "-" where undefined for the type of conic
+    let fmt_opt = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{:.2}", v));
+
     format!("{} {}:\n\
         p: {:.2},{:.2},{:.2}, v: {:.2},{:.2},{:.2}\n\
         h: {:.2}, i: {:.2}°, e: {:.2}\n\
         Ω: {:.2}°, ω: {:.2}°, ν: {:.2}°\n\
-        t_ν: {:.2}°, t: {:.2}",
+        t_ν: {:.2}°, t: {:.2}\n\
+        a: {:.2}, slr: {:.2}, ε: {:.3}, n: {:.4}\n\
+        r_p: {:.2}, r_a: {:.2}\n\
+        alt_p: {:.2}, alt_a: {:.2}\n\
+        t_p: {}, t_a: {}\n\
+        v_∞: {}, δ: {}",
         body_type, body_idx,
-        sat_transform.translation.x, sat_transform.translation.y, sat_transform.translation.z,
+        body_pos.x, body_pos.y, body_pos.z,
         body_vel.x, body_vel.y, body_vel.z,
         conic.h, deg!(conic.i), conic.e,
         deg!(conic.big_omega), deg!(conic.omega), deg!(conic.initial_nu),
-        deg!(t_nu), t)
+        deg!(t_nu), t,
+        conic.semi_major_axis(), conic.semi_latus_rectum(), conic.specific_energy(), conic.mean_motion(),
+        conic.periapsis_radius(), conic.apoapsis_radius(),
+        conic.periapsis_altitude(parent_radius), conic.apoapsis_altitude(parent_radius),
+        fmt_opt(conic.time_to_periapsis(current_time)), fmt_opt(conic.time_to_apoapsis(current_time)),
+        fmt_opt(conic.v_inf()), fmt_opt(conic.turning_angle().map(|d| deg!(d))))
+}
+
+// time warp buttons of the overlay, as the keyboard's time warp controls
+fn update_time_warp_buttons(
+    mut simulation_state: ResMut<SimulationState>,
+    button_query: Query<(&Interaction, &OverlayUiTimeWarpButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in &button_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            OverlayUiTimeWarpButton::Decrease => simulation_state.change_time_warp(false),
+            OverlayUiTimeWarpButton::Pause => simulation_state.paused = !simulation_state.paused,
+            OverlayUiTimeWarpButton::Increase => simulation_state.change_time_warp(true),
+        }
+    }
 }
 
 fn update_demo_controls(
     mut controls: ResMut<OverylayUiControls>,
+    mut simulation_state: ResMut<SimulationState>,
     mut body_info_query: Query<&mut Text, With<OverlayUiBodyInfo>>,
+    hierarchy: Res<BodyHierarchy>,
     config_handles: Res<SettingsConfigAssets>,
     config_assets: Res<Assets<SettingsConfigAsset>>,
     mut camera_query: Query<&mut Transform, With<GameCamera>>,
@@ -314,13 +754,24 @@ fn update_demo_controls(
 ) {
     let settings = config_assets.get(config_handles.settings.clone()).unwrap();
 
-    // check for tab for next body
+    // time warp
+    if key.just_pressed(KeyCode::Period) {
+        simulation_state.change_time_warp(true);
+    }
+    if key.just_pressed(KeyCode::Comma) {
+        simulation_state.change_time_warp(false);
+    }
+    if key.just_pressed(KeyCode::Space) {
+        simulation_state.paused = !simulation_state.paused;
+    }
+
+    // check for tab for next body, the root body is shown when none is selected
     if key.just_pressed(KeyCode::Tab) {
-        if let ViewingBody::Moon(idx) = controls.viewing_body {
-            if idx + 1 == settings.moons.len() {
+        if let ViewingBody::Body(idx) = controls.viewing_body {
+            if idx + 1 == hierarchy.bodies.len() {
                 controls.viewing_body = ViewingBody::Satellite(0)
             } else {
-                controls.viewing_body = ViewingBody::Moon(idx + 1);
+                controls.viewing_body = ViewingBody::Body(idx + 1);
             }
         } else if let ViewingBody::Satellite(idx) = controls.viewing_body {
             if idx + 1 == settings.satellites.len() {
@@ -329,8 +780,8 @@ fn update_demo_controls(
                 controls.viewing_body = ViewingBody::Satellite(idx + 1);
             }
         } else {
-            if 0 < settings.moons.len() {
-                controls.viewing_body = ViewingBody::Moon(0);
+            if 1 < hierarchy.bodies.len() {
+                controls.viewing_body = ViewingBody::Body(1);
             } else if 0 < settings.satellites.len() {
                 controls.viewing_body = ViewingBody::Satellite(0);
             }
@@ -340,11 +791,12 @@ fn update_demo_controls(
     // body info if no sat
     if ViewingBody::None == controls.viewing_body {
         let mut body_info = body_info_query.single_mut();
-        // body_info.sections[0].value = format!("Body:\n\
-        //     p: {:.2},{:.2},{:.2}\n\
-        //     m: {:.2}",
-        //     0., 0., 0., settings.body_mass);
-            // TODO restore body info
+        if let Some(root) = hierarchy.bodies.first() {
+            body_info.sections[0].value = format!("{}:\n\
+                p: {:.2},{:.2},{:.2}\n\
+                m: {:.2}, r: {:.2}",
+                root.name, 0., 0., 0., root.mass, root.radius);
+        }
 
         // update camera
         let mut camera_transform = camera_query.single_mut();
@@ -353,90 +805,135 @@ fn update_demo_controls(
     }
 }
 
+// end time of the drawn path of a conic from start time, after one revolution for closed
+// orbits, otherwise on passing MAX_PATH_RADIUS
+fn path_end_time(
+    conic: &OrbitConic,
+    start_time: f64,
+) -> f64 {
+    if conic.e < 1. {
+        start_time + conic.period
+    } else {
+        let nu_limit = f64::acos(((conic.semi_latus_rectum() / MAX_PATH_RADIUS - 1.) / conic.e).clamp(-1., 1.));
+        let theta_start = conic.nu_at_pos(conic.pos_at_t(start_time)) - conic.initial_nu;
+        (start_time + conic.t_at_theta(nu_limit - conic.initial_nu) - conic.t_at_theta(theta_start)).max(start_time)
+    }
+}
+
+// draw the predicted path of a conic about the given body, with its body at center.
+// when patch_soi is set the path continues on a new conic from the exact time it leaves
+// the body's soi or enters one of its children's
+#[allow(clippy::too_many_arguments)]
 fn draw_conic_path(
     conic: OrbitConic,
-    parent_info: Option<ParentInfo>,
+    start_time: f64,
+    body: usize,
+    center: DVec3,
+    maneuver: Option<ManeuverNode>,
+    hierarchy: &BodyHierarchy,
+    patch_soi: bool,
     gizmos: &mut Gizmos,
     color: Color,
-    other_bodies: Option<Vec<&OrbitConic>>,
     render_depth: i32,
 ) {
     const STEPS: i32 = 128;
-    let mut soi_change: Option<(OrbitConic,Option<ParentInfo>)> = None;
+    let mut soi_change: Option<(OrbitConic,usize,DVec3,Option<ManeuverNode>)> = None;
+
+    // sweep from the position at start time, one revolution ahead
+    let theta_start = conic.nu_at_pos(conic.pos_at_t(start_time)) - conic.initial_nu;
+    let t_theta_start = conic.t_at_theta(theta_start);
+
+    // exact time of the next soi change within the drawn part of the path, and of impact
+    // with the body's surface before it, which ends the path
+    let t_end = path_end_time(&conic, start_time);
+    let transition = if patch_soi {
+        hierarchy.next_soi_transition(body, &conic, start_time, t_end)
+    } else {
+        None
+    };
+    let impact = if patch_soi {
+        hierarchy.next_impact(body, &conic, start_time, transition.map_or(t_end, |(t, _)| t))
+    } else {
+        None
+    };
+
+    // mark periapsis of a flyby, unless the path ends on the surface first
+    let flyby = if patch_soi && impact.is_none() {
+        hierarchy.flyby_report(body, &conic, start_time)
+    } else {
+        None
+    };
+    if let Some(flyby) = flyby.filter(|flyby| flyby.t_periapsis > start_time) {
+        gizmos.sphere((center + conic.pos_at_t(flyby.t_periapsis)).as_vec3(), Quat::IDENTITY, 0.03, color);
+    }
+
     'conic_loop: for n in 0..STEPS {
         // sweep arc segment
-        let theta1 = (n as f32 - 0.5) * 2. * PI / (STEPS as f32);
-        let theta2 = (n as f32 + 0.5) * 2. * PI / (STEPS as f32);
+        let theta1 = theta_start + (n as f64) * 2. * PI / (STEPS as f64);
+        let theta2 = theta_start + (n as f64 + 1.) * 2. * PI / (STEPS as f64);
         let r1 = conic.r_at_theta(theta1);
-        if r1 > 30. || r1 < 0. {
+        if r1 < 0. {
             continue;
         }
         let r2 = conic.r_at_theta(theta2);
+        let t2 = start_time + conic.t_at_theta(theta2) - t_theta_start;
         // starting pos to ending pos in space
-        const NUDGE: f32 = 0.05; // todo wtf
-        let d1 = conic.dir_at_theta(theta1 + NUDGE);
-        let d2 = conic.dir_at_theta(theta2 + NUDGE);
-        let d2_pos = d2 * r2;
-
-        // orbit center of parent body
-        let orbit_center = if let Some(pi) = parent_info.as_ref() {
-            let mean_t = conic.t_at_nu((theta1 + theta2) / 2.);
-            pi.conic.pos_at_theta(pi.conic.nu_at_t(mean_t + pi.entry_time) - pi.conic.initial_nu) -
-                pi.conic.pos_at_theta(pi.conic.nu_at_t(pi.entry_time) - pi.conic.initial_nu)
-        } else {
-            Vec3::ZERO
-        };
+        let d1_pos = conic.dir_at_theta(theta1) * r1;
+        let mut d2_pos = conic.dir_at_theta(theta2) * r2;
+
+        // maneuver node, impact or soi change within this segment ends the arc at whichever
+        // is first, impacts are only found before the soi change
+        let t_event = impact.map(|impact| impact.t).or(transition.map(|(t, _)| t));
+        let burn = maneuver.filter(|node| node.t <= t2 && !t_event.is_some_and(|t| node.t > t));
+        let hit = impact.filter(|impact| impact.t <= t2 && burn.is_none());
+        let crossing = transition.filter(|(t, _)| *t <= t2 && burn.is_none() && impact.is_none());
+        if let Some(node) = burn {
+            d2_pos = conic.pos_at_t(node.t);
+        } else if let Some(impact) = hit {
+            d2_pos = conic.pos_at_t(impact.t);
+        } else if let Some((t, _)) = crossing {
+            d2_pos = conic.pos_at_t(t);
+        }
 
-        // draw ray
-        gizmos.ray(
-            orbit_center + d1 * r2,
-            d2 * r2 - d1 * r1,
-            color,
-        );
-
-
-        // check if d2 intersects other_bodies soi
-        if let Some(other_bodies_u) = other_bodies.as_ref() {
-            let d2_tu = conic.t_at_nu(theta2);
-            let d2_t = if d2_tu < 0. { conic.period + d2_tu } else { d2_tu };
-
-            for other_body in other_bodies_u {
-                let o_nuu = other_body.nu_at_t(d2_t);
-                let o_nu = if o_nuu < 0. { 2. * PI + o_nuu } else { o_nuu };
-                let ob_pos = other_body.pos_at_theta(o_nu);
-                let soi_r = MOON_SOI; // TODO
-                if d2_pos.distance(ob_pos) < soi_r {
-                    let sub_orbit = OrbitConic::from_initial(
-                        d2_pos,
-                        conic.vel_at_theta(theta2),
-                        MOON_MASS, // TODO as property of moon
-                        Vec3::Y);
-                    soi_change = Some((sub_orbit,Some(ParentInfo { conic: **other_body, entry_time: d2_t })));
-                    break 'conic_loop;
-                }
-            }
+        // draw ray, arcs beyond MAX_PATH_RADIUS are only checked for events
+        if r1 <= MAX_PATH_RADIUS {
+            gizmos.ray(
+                (center + d1_pos).as_vec3(),
+                (d2_pos - d1_pos).as_vec3(),
+                color,
+            );
         }
 
-        // check if d2 exits current soi
-        if parent_info.is_some() {
-            let soi_r = MOON_SOI; // TODO
-            if r2 > soi_r {
-                let out_orbit = OrbitConic::from_initial(
-                    d2_pos,
-                    conic.vel_at_theta(theta2),
-                    PLANET_MASS, // TODO as property of parent body
-                    Vec3::Y);
-                soi_change = Some((out_orbit,None));
-                break 'conic_loop;
-            }
+        // mark the point of impact, the path ends there
+        if hit.is_some() {
+            gizmos.sphere((center + d2_pos).as_vec3(), Quat::IDENTITY, 0.05, Color::RED);
+            break 'conic_loop;
+        }
+
+        // continue on the conic after the burn
+        if let Some(node) = burn {
+            soi_change = Some((node.apply(&conic), body, center, None));
+            break 'conic_loop;
+        }
+
+        // continue on a conic about the new body after leaving the body's soi or entering
+        // a child's, drawn relative to this path's center. burns are planned about this body
+        // and dropped
+        if let Some((t, new_body)) = crossing {
+            let (pos, vel) = hierarchy.change_frame(body, new_body, t, d2_pos, conic.vel_at_t(t));
+            let new_center = center + d2_pos - pos;
+            soi_change = Some((hierarchy.conic_about(new_body, t, pos, vel), new_body, new_center, None));
+            break 'conic_loop;
         }
 
     }
 
-    if let Some((conic, parent_info)) = soi_change {
-        draw_conic_path(conic, parent_info, gizmos,
+    if render_depth + 1 >= MAX_PATCH_DEPTH {
+        return;
+    }
+    if let Some((conic, body, center, maneuver)) = soi_change {
+        draw_conic_path(conic, conic.epoch, body, center, maneuver, hierarchy, patch_soi, gizmos,
             color.with_s(f32::powi(0.5, render_depth + 1)),
-            None,
             render_depth + 1);
     }
 }
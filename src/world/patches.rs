@@ -0,0 +1,221 @@
+use bevy::math::DVec3;
+
+use super::{BodyHierarchy, Impact, OrbitConic};
+
+// how a trajectory patch about a body ends
+#[derive(Clone, Copy, Debug)]
+pub enum PatchOutcome {
+    Bound, // closed orbit meeting nothing within one revolution
+    Escape, // open orbit about the root body, leaving the system
+    Capture(usize), // enters the soi of a child body
+    SoiExit(usize), // leaves the body's soi into its parent's
+    Impact(Impact), // reaches the body's surface
+}
+
+// part of a trajectory on a single conic about a body, from t_start until its outcome at t_end,
+// infinite for bound and escaping patches
+#[derive(Clone, Copy, Debug)]
+pub struct TrajectoryPatch {
+    pub body: usize,
+    pub conic: OrbitConic,
+    pub t_start: f64,
+    pub t_end: f64,
+    pub outcome: PatchOutcome,
+}
+
+impl OrbitConic {
+
+    // simulation time an open conic passes the given distance from its body on the way out,
+    // none for closed conics
+    pub fn outbound_time_at_radius(
+        &self,
+        radius: f64,
+    ) -> Option<f64> {
+        if self.e < 1. {
+            return None;
+        }
+        let nu = f64::acos(((self.semi_latus_rectum() / radius - 1.) / self.e).clamp(-1., 1.));
+        Some(self.t_at_theta(nu - self.initial_nu))
+    }
+
+}
+
+impl BodyHierarchy {
+
+    // distance from body idx beyond which an object meets neither it nor any of its children
+    // at simulation time t, the soi radius for all but the root. children on open conics only
+    // reach as far as their distance at t
+    pub fn reach_radius(
+        &self,
+        idx: usize,
+        t: f64,
+    ) -> f64 {
+        let body = &self.bodies[idx];
+        if body.parent.is_some() {
+            return body.soi_radius;
+        }
+        self.children(idx)
+            .map(|child| {
+                let furthest = match self.body_conic_at(child, t) {
+                    Some(conic) if conic.e < 1. => conic.apoapsis_radius(),
+                    _ => self.body_state_at(child, t).0.length(),
+                };
+                furthest + self.bodies[child].soi_radius
+            })
+            .fold(body.radius, f64::max)
+    }
+
+    // whether an object at a state relative to the root at simulation time t is leaving the
+    // system for good, on an open orbit, outbound and beyond the reach of every body
+    pub fn is_escaping(
+        &self,
+        conic: &OrbitConic,
+        t: f64,
+        position: DVec3,
+        velocity: DVec3,
+    ) -> bool {
+        conic.e >= 1. && position.dot(velocity) > 0. && position.length() > self.reach_radius(0, t)
+    }
+
+    // patch of an object on conic about body idx from t_start and how it ends. closed orbits are
+    // followed for one revolution, open orbits until beyond reach of the body and its children
+    pub fn classify_patch(
+        &self,
+        idx: usize,
+        conic: &OrbitConic,
+        t_start: f64,
+    ) -> TrajectoryPatch {
+        let t_end = if conic.e < 1. {
+            t_start + conic.period
+        } else {
+            conic.outbound_time_at_radius(self.reach_radius(idx, t_start)).unwrap().max(t_start)
+        };
+        let transition = self.next_soi_transition(idx, conic, t_start, t_end);
+        let impact = self.next_impact(idx, conic, t_start, transition.map_or(t_end, |(t, _)| t));
+        let (t_end, outcome) = match (impact, transition) {
+            (Some(impact), _) => (impact.t, PatchOutcome::Impact(impact)),
+            (None, Some((t, body))) if Some(body) == self.bodies[idx].parent => (t, PatchOutcome::SoiExit(body)),
+            (None, Some((t, body))) => (t, PatchOutcome::Capture(body)),
+            (None, None) if conic.e < 1. => (f64::INFINITY, PatchOutcome::Bound),
+            // open orbits leave any soi but the root's, at the end of the window
+            (None, None) => match self.bodies[idx].parent {
+                Some(parent) => (t_end, PatchOutcome::SoiExit(parent)),
+                None => (f64::INFINITY, PatchOutcome::Escape),
+            },
+        };
+        TrajectoryPatch { body: idx, conic: *conic, t_start, t_end, outcome }
+    }
+
+    // successive patches of an object on conic about body idx from t_start, following captures
+    // and soi exits until the trajectory ends or max_patches are found
+    pub fn trajectory_patches(
+        &self,
+        idx: usize,
+        conic: &OrbitConic,
+        t_start: f64,
+        max_patches: usize,
+    ) -> Vec<TrajectoryPatch> {
+        let mut patches: Vec<TrajectoryPatch> = Vec::new();
+        let mut patch = self.classify_patch(idx, conic, t_start);
+        while patches.len() < max_patches {
+            patches.push(patch);
+            let (PatchOutcome::Capture(new_body) | PatchOutcome::SoiExit(new_body)) = patch.outcome else {
+                break;
+            };
+            let (pos, vel) = patch.conic.state_at(patch.t_end);
+            let (pos, vel) = self.change_frame(patch.body, new_body, patch.t_end, pos, vel);
+            let new_conic = self.conic_about(new_body, patch.t_end, pos, vel);
+            patch = self.classify_patch(new_body, &new_conic, patch.t_end);
+        }
+        patches
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{lambert_solver, test_hierarchy, LambertPath, SoiModel, G};
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_outbound_time_at_radius() {
+        let test_oc = OrbitConic::from_initial(
+            DVec3::new(7000., 0., 0.),
+            DVec3::new(0., 12., 0.),
+            398600. / G,
+            DVec3::Z,
+        ).with_epoch(10.);
+        let t = test_oc.outbound_time_at_radius(20000.).unwrap();
+        assert!(t > 10.);
+        assert_f!(test_oc.pos_at_t(t).length(), 20000.);
+        assert!(test_oc.pos_at_t(t).dot(test_oc.vel_at_t(t)) > 0.);
+        // closed orbits have no outbound crossing
+        let closed_oc = OrbitConic::from_initial(
+            DVec3::new(7000., 0., 0.), DVec3::new(0., 8., 0.), 398600. / G, DVec3::Z);
+        assert!(closed_oc.outbound_time_at_radius(20000.).is_none());
+    }
+
+    #[test]
+    fn test_classify_patch() {
+        let hierarchy = test_hierarchy();
+
+        // circular orbit about the planet inside the moon's orbit
+        let conic = hierarchy.conic_about(1, 0., DVec3::new(0., 5e3, 0.), DVec3::new(-f64::sqrt(1e16 / 5e3), 0., 0.));
+        assert!(matches!(hierarchy.classify_patch(1, &conic, 0.).outcome, PatchOutcome::Bound));
+
+        // falling onto the planet
+        let conic = hierarchy.conic_about(1, 0., DVec3::new(0., 5e3, 0.), DVec3::new(-100., 0., 0.));
+        let patch = hierarchy.classify_patch(1, &conic, 0.);
+        let PatchOutcome::Impact(impact) = patch.outcome else {
+            panic!("expected impact");
+        };
+        assert_eq!(patch.t_end, impact.t);
+        assert_f!(conic.pos_at_t(impact.t).length(), 100.);
+
+        // transfer to the moon
+        let moon_conic = hierarchy.bodies[2].conic.unwrap();
+        let r1 = DVec3::new(0., 5e4, 0.);
+        let r2 = moon_conic.pos_at_t(1.);
+        let path = LambertPath::prograde(r1, r2, DVec3::Z);
+        let conic = lambert_solver(r1, r2, 1., path, 0, hierarchy.bodies[1].mass, DVec3::Z)[0];
+        let patch = hierarchy.classify_patch(1, &conic, 0.);
+        assert!(matches!(patch.outcome, PatchOutcome::Capture(2)));
+        assert!(patch.t_end < 1.);
+
+        // fast escape from the planet, then from the star
+        let conic = hierarchy.conic_about(1, 0., DVec3::new(0., 1e4, 0.), DVec3::new(1e5, 2e6, 0.));
+        let patches = hierarchy.trajectory_patches(1, &conic, 0., 4);
+        assert_eq!(patches.len(), 2);
+        assert!(matches!(patches[0].outcome, PatchOutcome::SoiExit(0)));
+        assert_eq!(patches[1].body, 0);
+        assert_eq!(patches[1].t_start, patches[0].t_end);
+        assert!(matches!(patches[1].outcome, PatchOutcome::Escape));
+
+        // leaving for good once beyond every body's reach
+        let (pos, vel) = patches[1].conic.state_at(patches[1].t_start);
+        assert!(!hierarchy.is_escaping(&patches[1].conic, patches[1].t_start, pos, vel));
+        let t = patches[1].conic.outbound_time_at_radius(hierarchy.reach_radius(0, 0.) * 2.).unwrap();
+        let (pos, vel) = patches[1].conic.state_at(t);
+        assert!(hierarchy.is_escaping(&patches[1].conic, t, pos, vel));
+
+        // a body leaving on an open conic reaches only as far as it has gone
+        let mut hierarchy = test_hierarchy();
+        hierarchy.add_body("Comet", 0, 1e8 / G, 1.,
+            DVec3::new(0., 1e8, 0.), DVec3::new(2e6, 0., 0.),
+            DVec3::Z, SoiModel::Laplace);
+        assert!(hierarchy.bodies[3].soi_radius.is_finite());
+        let reach = hierarchy.reach_radius(0, 0.);
+        assert!(reach.is_finite());
+        assert!(hierarchy.reach_radius(0, 1e6) > reach);
+    }
+}
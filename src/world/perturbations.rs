@@ -0,0 +1,151 @@
+use bevy::math::{DQuat, DVec3};
+
+use super::{BodyHierarchy, OrbitConic, G};
+
+// acceleration from the J2 (oblateness) term of a body's gravity at a position relative to its
+// center, with the body's axis of symmetry along body_plane_k
+pub fn j2_acceleration(
+    position: DVec3,
+    body_mass: f64,
+    j2: f64,
+    body_radius: f64,
+    body_plane_k: DVec3,
+) -> DVec3 {
+    let r = position.length();
+    let z = position.dot(body_plane_k);
+    let scale = -1.5 * j2 * G * body_mass * body_radius.powi(2) / r.powi(5);
+    scale * ((1. - 5. * z.powi(2) / r.powi(2)) * position + 2. * z * body_plane_k)
+}
+
+impl OrbitConic {
+
+    // secular rates of change of the right ascension of the ascending node and of the argument
+    // of periapsis due to J2, for closed orbits
+    pub fn j2_rates(
+        &self,
+        j2: f64,
+        body_radius: f64,
+    ) -> (f64, f64) {
+        if self.e >= 1. {
+            return (0., 0.);
+        }
+        let factor = self.mean_motion() * j2 * (body_radius / self.semi_latus_rectum()).powi(2);
+        let cos_i = self.i.cos();
+        (-1.5 * factor * cos_i, 0.75 * factor * (5. * cos_i.powi(2) - 1.))
+    }
+
+    // conic with its node and periapsis advanced by the J2 secular rates from the epoch
+    // to simulation time t (mean element propagation), shape and epoch are unchanged
+    pub fn j2_precessed(
+        &self,
+        t: f64,
+        j2: f64,
+        body_radius: f64,
+    ) -> OrbitConic {
+        let (big_omega_rate, omega_rate) = self.j2_rates(j2, body_radius);
+        let dt = t - self.epoch;
+        if big_omega_rate == 0. && omega_rate == 0. {
+            return *self;
+        }
+        // periapsis turns within the orbital plane, then the plane turns about the body's axis
+        let rotation = DQuat::from_axis_angle(self.body_plane_k, big_omega_rate * dt)
+            * DQuat::from_axis_angle(self.h_vec.normalize(), omega_rate * dt);
+        OrbitConic::from_initial(
+            rotation * self.initial_r,
+            rotation * self.initial_v,
+            self.body_mass,
+            self.body_plane_k)
+            .with_epoch(self.epoch)
+    }
+
+}
+
+impl BodyHierarchy {
+
+    // conic about body idx with the body's J2 secular drift applied up to simulation time t
+    pub fn j2_precessed(
+        &self,
+        idx: usize,
+        conic: &OrbitConic,
+        t: f64,
+    ) -> OrbitConic {
+        let body = &self.bodies[idx];
+        if body.j2 == 0. {
+            return *conic;
+        }
+        conic.j2_precessed(t, body.j2, body.radius)
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    const EARTH_J2: f64 = 1.08263e-3;
+    const EARTH_RADIUS: f64 = 6378.;
+
+    #[test]
+    fn test_j2_rates() {
+        // curtis example 4.9, 280 km by 400 km orbit at 51.43°
+        let r_p = EARTH_RADIUS + 280.;
+        let r_a = EARTH_RADIUS + 400.;
+        let test_oc = OrbitConic::from_elements(
+            (r_p + r_a) / 2.,
+            (r_a - r_p) / (r_a + r_p),
+            51.43_f64.to_radians(),
+            0.,
+            0.,
+            0.,
+            398600. / G,
+            DVec3::Z,
+        );
+        let (big_omega_rate, omega_rate) = test_oc.j2_rates(EARTH_J2, EARTH_RADIUS);
+        assert_f!(big_omega_rate.to_degrees() * 86400., -5.1807237);
+        assert_f!(omega_rate.to_degrees() * 86400., 3.9203208);
+
+        // no apse line drift at the critical inclination
+        let critical_oc = OrbitConic::from_elements(
+            7000., 0.1, f64::acos(f64::sqrt(1. / 5.)), 0., 0., 0., 398600. / G, DVec3::Z);
+        assert!(critical_oc.j2_rates(EARTH_J2, EARTH_RADIUS).1.abs() < 1e-15);
+    }
+
+    #[test]
+    fn test_j2_precessed() {
+        let test_oc = OrbitConic::from_elements(
+            7000., 0.05, 0.5, 1., 2., 0.3, 398600. / G, DVec3::Z);
+        let (big_omega_rate, omega_rate) = test_oc.j2_rates(EARTH_J2, EARTH_RADIUS);
+        let t = 86400.;
+        let precessed_oc = test_oc.j2_precessed(t, EARTH_J2, EARTH_RADIUS);
+        assert_f!(precessed_oc.big_omega, (1. + big_omega_rate * t).rem_euclid(2. * PI));
+        assert_f!(precessed_oc.omega, (2. + omega_rate * t).rem_euclid(2. * PI));
+        assert_f!(precessed_oc.e, test_oc.e);
+        assert_f!(precessed_oc.i, test_oc.i);
+        assert_f!(precessed_oc.h, test_oc.h);
+        assert_f!(precessed_oc.epoch + 1., 0. + 1.);
+    }
+
+    #[test]
+    fn test_j2_acceleration() {
+        let mass = 398600. / G;
+        let r: f64 = 7000.;
+        let scale = EARTH_J2 * 398600. * EARTH_RADIUS.powi(2) / r.powi(4);
+        // pulls inwards over the equator
+        let accel = j2_acceleration(DVec3::new(r, 0., 0.), mass, EARTH_J2, EARTH_RADIUS, DVec3::Z);
+        assert_f!(accel.x, -1.5 * scale);
+        // weakens gravity over the poles
+        let accel = j2_acceleration(DVec3::new(0., 0., r), mass, EARTH_J2, EARTH_RADIUS, DVec3::Z);
+        assert_f!(accel.z, 3. * scale);
+    }
+}
@@ -0,0 +1,102 @@
+use serde::Deserialize;
+
+use super::OrbitConic;
+
+// approximation used for the radius within which a body's gravity dominates its parent's
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum SoiModel {
+    #[default]
+    Laplace, // a * (m / M)^(2/5), the patched conics convention
+    Hill, // a * (1 - e) * (m / 3M)^(1/3), the region of stable satellite orbits
+}
+
+// laplace sphere of influence radius of a body of mass m orbiting a parent of mass big_m
+pub fn laplace_soi_radius(
+    semi_major_axis: f64,
+    m: f64,
+    big_m: f64,
+) -> f64 {
+    semi_major_axis * (m / big_m).powf(2. / 5.)
+}
+
+// hill sphere radius of a body of mass m orbiting a parent of mass big_m, at periapsis
+pub fn hill_sphere_radius(
+    semi_major_axis: f64,
+    e: f64,
+    m: f64,
+    big_m: f64,
+) -> f64 {
+    semi_major_axis * (1. - e) * (m / (3. * big_m)).cbrt()
+}
+
+impl OrbitConic {
+
+    // sphere of influence radius of a body with given mass travelling on this conic,
+    // the conic's body mass is that of the parent. open orbits have no semi-major axis to
+    // scale by, so their radius is taken at the initial distance from the parent
+    pub fn soi_radius(
+        &self,
+        body_mass: f64,
+        model: SoiModel,
+    ) -> f64 {
+        if self.e >= 1. {
+            let distance = self.initial_r.length();
+            return match model {
+                SoiModel::Laplace => laplace_soi_radius(distance, body_mass, self.body_mass),
+                SoiModel::Hill => hill_sphere_radius(distance, 0., body_mass, self.body_mass),
+            };
+        }
+        match model {
+            SoiModel::Laplace => laplace_soi_radius(self.semi_major_axis(), body_mass, self.body_mass),
+            SoiModel::Hill => hill_sphere_radius(self.semi_major_axis(), self.e, body_mass, self.body_mass),
+        }
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::G;
+    use bevy::math::DVec3;
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_soi_radius() {
+        // earth about the sun, roughly 925,000 km as in curtis section 8.2
+        let earth_mass = 5.974e24;
+        let sun_mass = 1.989e30;
+        let r_earth = 149.6e6;
+        assert_f!(laplace_soi_radius(r_earth, earth_mass, sun_mass), 924663.61259);
+
+        // circular orbit of the earth about the sun
+        let v_circular = f64::sqrt(G * sun_mass / r_earth);
+        let earth_oc = OrbitConic::from_initial(
+            DVec3::new(r_earth, 0., 0.),
+            DVec3::new(0., v_circular, 0.),
+            sun_mass,
+            DVec3::Z,
+        );
+        assert_f!(earth_oc.soi_radius(earth_mass, SoiModel::Laplace), laplace_soi_radius(r_earth, earth_mass, sun_mass));
+        assert_f!(earth_oc.soi_radius(earth_mass, SoiModel::Hill), 1496584.7666);
+
+        // escaping bodies are bounded at their initial distance
+        let escape_oc = OrbitConic::from_initial(
+            DVec3::new(r_earth, 0., 0.),
+            DVec3::new(0., 2. * v_circular, 0.),
+            sun_mass,
+            DVec3::Z,
+        );
+        assert_f!(escape_oc.soi_radius(earth_mass, SoiModel::Laplace), laplace_soi_radius(r_earth, earth_mass, sun_mass));
+        assert!(escape_oc.soi_radius(earth_mass, SoiModel::Hill).is_finite());
+    }
+}
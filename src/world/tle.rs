@@ -0,0 +1,273 @@
+use bevy::math::DVec3;
+use std::f64::consts::PI;
+use thiserror::Error;
+
+use super::{OrbitConic, G};
+
+// seconds per day, tle mean motion is in revolutions per day
+const SECONDS_PER_DAY: f64 = 86400.;
+
+// norad two-line element set, angles in radians
+// https://celestrak.org/columns/v04n03/
+#[derive(Clone, Debug)]
+pub struct Tle {
+    pub name: Option<String>, // from the optional title line
+    pub catalog_number: u32,
+    pub classification: char,
+    pub international_designator: String,
+    pub epoch_year: i32, // four digit year
+    pub epoch_day: f64, // day of the year and fraction, 1. at the start of january 1st
+    pub mean_motion_dot: f64, // first derivative of mean motion / 2, revolutions per day²
+    pub mean_motion_ddot: f64, // second derivative of mean motion / 6, revolutions per day³
+    pub bstar: f64, // drag term, inverse earth radii
+    pub element_set_number: u32,
+    pub inclination: f64,
+    pub raan: f64, // right ascension of the ascending node
+    pub eccentricity: f64,
+    pub argument_of_perigee: f64,
+    pub mean_anomaly: f64,
+    pub mean_motion: f64, // revolutions per day
+    pub revolution_number: u32,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum TleError {
+    #[error("Missing line {0} of element set")]
+    MissingLine(u32),
+    #[error("Line {0} of element set is not 69 characters")]
+    LineLength(u32),
+    #[error("Checksum mismatch on line {0}")]
+    Checksum(u32),
+    #[error("Could not parse {0}")]
+    Field(&'static str),
+    #[error("Catalog numbers of the two lines differ")]
+    CatalogMismatch,
+}
+
+impl Tle {
+
+    // parse the first element set of text, two lines optionally preceded by a title line
+    pub fn parse(
+        text: &str,
+    ) -> Result<Tle, TleError> {
+        let lines: Vec<&str> = text.lines().map(|line| line.trim_end()).filter(|line| !line.is_empty()).collect();
+        Self::parse_lines(&lines).map(|(tle, _)| tle)
+    }
+
+    // parse every element set of text
+    pub fn parse_all(
+        text: &str,
+    ) -> Result<Vec<Tle>, TleError> {
+        let lines: Vec<&str> = text.lines().map(|line| line.trim_end()).filter(|line| !line.is_empty()).collect();
+        let mut tles = Vec::new();
+        let mut remaining = &lines[..];
+        while !remaining.is_empty() {
+            let (tle, used) = Self::parse_lines(remaining)?;
+            tles.push(tle);
+            remaining = &remaining[used..];
+        }
+        Ok(tles)
+    }
+
+    // parse the element set at the start of lines, returning the number of lines used
+    fn parse_lines(
+        lines: &[&str],
+    ) -> Result<(Tle, usize), TleError> {
+        let (name, offset) = match lines.first() {
+            Some(line) if !line.starts_with("1 ") => (Some(line.trim_start_matches("0 ").trim().to_string()), 1),
+            _ => (None, 0),
+        };
+        let line1 = *lines.get(offset).ok_or(TleError::MissingLine(1))?;
+        let line2 = *lines.get(offset + 1).ok_or(TleError::MissingLine(2))?;
+        for (number, line) in [(1, line1), (2, line2)] {
+            if line.len() != 69 || !line.is_ascii() {
+                return Err(TleError::LineLength(number));
+            }
+            if !line.starts_with(&format!("{} ", number)) {
+                return Err(TleError::MissingLine(number));
+            }
+            if checksum(&line[..68]) != line[68..].parse::<u32>().map_err(|_| TleError::Field("checksum"))? {
+                return Err(TleError::Checksum(number));
+            }
+        }
+
+        // fixed columns, as 1-based inclusive ranges of the specification
+        let field = |line: &str, start: usize, end: usize| line[start - 1..end].trim().to_string();
+        let number = |line: &str, start: usize, end: usize, name: &'static str| {
+            field(line, start, end).parse::<f64>().map_err(|_| TleError::Field(name))
+        };
+        let integer = |line: &str, start: usize, end: usize, name: &'static str| {
+            let value = field(line, start, end);
+            if value.is_empty() { Ok(0) } else { value.parse::<u32>().map_err(|_| TleError::Field(name)) }
+        };
+
+        let catalog_number = integer(line1, 3, 7, "catalog number")?;
+        if integer(line2, 3, 7, "catalog number")? != catalog_number {
+            return Err(TleError::CatalogMismatch);
+        }
+        // two digit years from 57 are in the 1900s
+        let year = integer(line1, 19, 20, "epoch year")? as i32;
+        let tle = Tle {
+            name,
+            catalog_number,
+            classification: line1.as_bytes()[7] as char,
+            international_designator: field(line1, 10, 17),
+            epoch_year: if year < 57 { 2000 + year } else { 1900 + year },
+            epoch_day: number(line1, 21, 32, "epoch day")?,
+            mean_motion_dot: number(line1, 34, 43, "mean motion derivative")?,
+            mean_motion_ddot: implied_decimal(&field(line1, 45, 52)).ok_or(TleError::Field("mean motion second derivative"))?,
+            bstar: implied_decimal(&field(line1, 54, 61)).ok_or(TleError::Field("bstar"))?,
+            element_set_number: integer(line1, 65, 68, "element set number")?,
+            inclination: number(line2, 9, 16, "inclination")?.to_radians(),
+            raan: number(line2, 18, 25, "right ascension of the ascending node")?.to_radians(),
+            eccentricity: number(line2, 27, 33, "eccentricity")? / 1e7,
+            argument_of_perigee: number(line2, 35, 42, "argument of perigee")?.to_radians(),
+            mean_anomaly: number(line2, 44, 51, "mean anomaly")?.to_radians(),
+            mean_motion: number(line2, 53, 63, "mean motion")?,
+            revolution_number: integer(line2, 64, 68, "revolution number")?,
+        };
+        Ok((tle, offset + 2))
+    }
+
+    // epoch in seconds since J2000 (2000-01-01 12:00), ignoring leap seconds
+    pub fn epoch_j2000(
+        &self,
+    ) -> f64 {
+        let is_leap = |year: i32| (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+        let days_in_year = |year: i32| if is_leap(year) { 366. } else { 365. };
+        let mut days = self.epoch_day - 1.5;
+        for year in 2000..self.epoch_year {
+            days += days_in_year(year);
+        }
+        for year in self.epoch_year..2000 {
+            days -= days_in_year(year);
+        }
+        days * SECONDS_PER_DAY
+    }
+
+    // conic of the element set about a body with given mass, relative to the body's
+    // plane-of-reference, with its epoch at simulation time t_epoch. the mean elements are
+    // taken as osculating two-body elements, ignoring the drag and J2 terms of sgp4, so the
+    // conic is an approximation close to the epoch. distances are in meters for a body mass in
+    // kilograms, as G is in SI units
+    pub fn conic(
+        &self,
+        body_mass: f64,
+        body_plane_k: DVec3,
+        t_epoch: f64,
+    ) -> OrbitConic {
+        let n = self.mean_motion * 2. * PI / SECONDS_PER_DAY;
+        let a = f64::cbrt(G * body_mass / n.powi(2));
+        let (e, i, big_omega, omega) = (self.eccentricity, self.inclination, self.raan, self.argument_of_perigee);
+        // true anomaly from the time since periapsis of the mean anomaly
+        let periapsis_oc = OrbitConic::from_elements(a, e, i, big_omega, omega, 0., body_mass, body_plane_k);
+        let nu = periapsis_oc.nu_at_t(self.mean_anomaly / n);
+        OrbitConic::from_elements(a, e, i, big_omega, omega, nu, body_mass, body_plane_k)
+            .with_epoch(t_epoch)
+    }
+
+}
+
+// modulo 10 sum of the digits of a line, with minus signs counting as 1
+fn checksum(
+    line: &str,
+) -> u32 {
+    line.chars().map(|c| match c {
+        '-' => 1,
+        _ => c.to_digit(10).unwrap_or(0),
+    }).sum::<u32>() % 10
+}
+
+// value of a field with an implied leading decimal point and a power of ten exponent,
+// such as " 12345-4" for 0.12345e-4
+fn implied_decimal(
+    field: &str,
+) -> Option<f64> {
+    if field.is_empty() {
+        return Some(0.);
+    }
+    let split = field.rfind(['-', '+']).filter(|&idx| idx > 0)?;
+    let (mantissa, exponent) = field.split_at(split);
+    let (sign, digits) = match mantissa.strip_prefix('-') {
+        Some(digits) => (-1., digits),
+        None => (1., mantissa.trim_start_matches('+')),
+    };
+    let mantissa: f64 = format!("0.{}", digits.trim()).parse().ok()?;
+    let exponent: i32 = exponent.parse().ok()?;
+    Some(sign * mantissa * 10_f64.powi(exponent))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    const ISS_TLE: &str = "ISS (ZARYA)
+1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927
+2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537";
+
+    #[test]
+    fn test_parse_tle() {
+        let tle = Tle::parse(ISS_TLE).unwrap();
+        assert_eq!(tle.name.as_deref(), Some("ISS (ZARYA)"));
+        assert_eq!(tle.catalog_number, 25544);
+        assert_eq!(tle.classification, 'U');
+        assert_eq!(tle.international_designator, "98067A");
+        assert_eq!(tle.epoch_year, 2008);
+        assert_f!(tle.epoch_day, 264.51782528);
+        assert_f!(tle.mean_motion_dot, -0.00002182);
+        assert_eq!(tle.mean_motion_ddot, 0.);
+        assert_f!(tle.bstar, -0.11606e-4);
+        assert_eq!(tle.element_set_number, 292);
+        assert_f!(tle.inclination, 51.6416_f64.to_radians());
+        assert_f!(tle.eccentricity, 0.0006703);
+        assert_f!(tle.mean_motion, 15.72125391);
+        assert_eq!(tle.revolution_number, 56353);
+
+        // 2922 days from 2000 to 2008
+        assert_f!(tle.epoch_j2000(), (2922. + 264.51782528 - 1.5) * 86400.);
+
+        // without a title line, and several in one file
+        let lines: Vec<&str> = ISS_TLE.lines().collect();
+        assert!(Tle::parse(&lines[1..].join("\n")).unwrap().name.is_none());
+        assert_eq!(Tle::parse_all(&format!("{}\n{}", ISS_TLE, ISS_TLE)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_tle_errors() {
+        // altered digit fails the checksum
+        let altered = ISS_TLE.replace("51.6416", "51.6417");
+        assert!(matches!(Tle::parse(&altered), Err(TleError::Checksum(2))));
+        // truncated line
+        let truncated = ISS_TLE.replace("56353", "5635");
+        assert!(matches!(Tle::parse(&truncated), Err(TleError::LineLength(2))));
+        // missing second line
+        let lines: Vec<&str> = ISS_TLE.lines().collect();
+        assert!(matches!(Tle::parse(&lines[..2].join("\n")), Err(TleError::MissingLine(2))));
+    }
+
+    #[test]
+    fn test_tle_conic() {
+        let tle = Tle::parse(ISS_TLE).unwrap();
+        let test_oc = tle.conic(398600. / G, DVec3::Z, 100.);
+        assert_f!(test_oc.period, 86400. / 15.72125391);
+        assert_f!(test_oc.e, 0.0006703);
+        assert_f!(test_oc.i, tle.inclination);
+        assert_f!(test_oc.big_omega, tle.raan);
+        assert_f!(test_oc.omega, tle.argument_of_perigee);
+        assert_eq!(test_oc.epoch, 100.);
+        // mean anomaly from the time since periapsis
+        assert_f!(test_oc.t_at_nu(test_oc.initial_nu).rem_euclid(test_oc.period),
+            tle.mean_anomaly / (2. * PI) * test_oc.period);
+    }
+}
@@ -0,0 +1,236 @@
+use bevy::math::DVec3;
+use std::f64::consts::PI;
+
+use super::{BodyHierarchy, ManeuverNode, OrbitConic, G};
+
+// kind of transfer between two circular orbits about the same body
+#[derive(Clone, Copy, Debug)]
+pub enum TransferKind {
+    Hohmann, // half an ellipse touching both orbits
+    BiElliptic(f64), // half ellipses out to this radius, then back to the target orbit
+}
+
+// burns of a transfer, all prograde or retrograde
+#[derive(Clone, Debug)]
+pub struct TransferPlan {
+    pub burns: Vec<ManeuverNode>,
+    pub delta_v: f64, // total of the burns' magnitudes
+    pub transfer_time: f64, // from the first burn to arrival at the target orbit
+}
+
+impl TransferPlan {
+
+    // transfer from conic onto a circular orbit of target_radius, the first burn at time t1
+    // raising or lowering the opposite side of the orbit. the first leg departs at the
+    // distance and speed of the conic at t1, which should be an apsis so the burn is horizontal
+    pub fn from_burn_time(
+        conic: &OrbitConic,
+        t1: f64,
+        target_radius: f64,
+        kind: TransferKind,
+    ) -> Self {
+        let mu = G * conic.body_mass;
+        let (pos, vel) = conic.state_at(t1);
+        let radii = match kind {
+            TransferKind::Hohmann => vec![pos.length(), target_radius],
+            TransferKind::BiElliptic(intermediate_radius) => vec![pos.length(), intermediate_radius, target_radius],
+        };
+
+        // each leg is half of an ellipse between successive radii, from the vis-viva equation
+        let mut burns: Vec<ManeuverNode> = Vec::new();
+        let mut t = t1;
+        let mut speed = vel.length();
+        for leg in radii.windows(2) {
+            let a = (leg[0] + leg[1]) / 2.;
+            let departure_speed = f64::sqrt(mu * (2. / leg[0] - 1. / a));
+            burns.push(ManeuverNode { t, prograde: departure_speed - speed, normal: 0., radial: 0. });
+            t += PI * f64::sqrt(a.powi(3) / mu);
+            speed = f64::sqrt(mu * (2. / leg[1] - 1. / a));
+        }
+        // circularize at the target
+        burns.push(ManeuverNode { t, prograde: f64::sqrt(mu / target_radius) - speed, normal: 0., radial: 0. });
+
+        TransferPlan {
+            delta_v: burns.iter().map(|burn| burn.prograde.abs()).sum(),
+            transfer_time: t - t1,
+            burns,
+        }
+    }
+
+    // transfer from a closed conic onto a circular orbit of target_radius about the same body,
+    // departing from periapsis when raising the orbit or apoapsis when lowering it, at the
+    // first after after_t. none for open conics
+    pub fn to_radius(
+        conic: &OrbitConic,
+        target_radius: f64,
+        kind: TransferKind,
+        after_t: f64,
+    ) -> Option<Self> {
+        let time_to_apsis = if target_radius >= conic.periapsis_radius() {
+            conic.time_to_periapsis(after_t)
+        } else {
+            conic.time_to_apoapsis(after_t)
+        };
+        let t1 = after_t + time_to_apsis.filter(|_| conic.e < 1.)?;
+        Some(Self::from_burn_time(conic, t1, target_radius, kind))
+    }
+
+}
+
+impl BodyHierarchy {
+
+    // transfer from a conic about body idx to pass its child target with a periapsis
+    // periapsis_offset from its centre, outside the target's orbit or inside when negative,
+    // approximately for near-circular coplanar orbits. the transfer arrives beside the
+    // target's orbit by the miss distance giving that periapsis, timed so the target passes
+    // there. the plan ends on arrival, without circularizing about idx, as the object then
+    // enters the target's soi. none when target does not orbit idx, the conic is open or both
+    // have the same period
+    pub fn plan_transfer_to_body(
+        &self,
+        idx: usize,
+        conic: &OrbitConic,
+        target: usize,
+        kind: TransferKind,
+        periapsis_offset: f64,
+        after_t: f64,
+    ) -> Option<TransferPlan> {
+        if self.bodies[target].parent != Some(idx) || conic.e >= 1. {
+            return None;
+        }
+        let target_conic = self.body_conic_at(target, after_t)?;
+        let target_radius = target_conic.semi_major_axis();
+
+        // miss distance of the approach asymptote for the periapsis, taking the speed relative
+        // to the target on arriving at its orbit as the hyperbolic excess speed
+        let direct = TransferPlan::from_burn_time(conic, after_t, target_radius, kind);
+        let arrival_speed = f64::sqrt(G * conic.body_mass / target_radius) - direct.burns.last()?.prograde;
+        let v_inf = arrival_speed - target_conic.vel_at_t(after_t).length();
+        let rp = periapsis_offset.abs();
+        let miss_distance = periapsis_offset.signum() * rp * f64::sqrt(1. + 2. * G * self.bodies[target].mass / (rp * v_inf.powi(2)));
+        let arrival_radius = target_radius + miss_distance;
+
+        // angles within the conic's orbital plane, and the rate the target gains on the object
+        let (p_hat, q_hat, _) = conic.perifocal_basis();
+        let angle = |pos: DVec3| pos.dot(q_hat).atan2(pos.dot(p_hat));
+        let relative_rate = 2. * PI / target_conic.period - 2. * PI / conic.period;
+        if relative_rate == 0. {
+            return None;
+        }
+
+        // the target must lead the object at departure by its motion during the transfer,
+        // less the angle swept by the object over the legs of the transfer
+        let transfer_time = TransferPlan::from_burn_time(conic, after_t, arrival_radius, kind).transfer_time;
+        let legs = match kind {
+            TransferKind::Hohmann => 1.,
+            TransferKind::BiElliptic(_) => 2.,
+        };
+        let lead = angle(target_conic.pos_at_t(after_t)) - angle(conic.pos_at_t(after_t));
+        let required_lead = legs * PI - 2. * PI / target_conic.period * transfer_time;
+        let wait = ((required_lead - lead) / relative_rate).rem_euclid(2. * PI / relative_rate.abs());
+
+        // drop the circularizing burn at arrival
+        let mut plan = TransferPlan::from_burn_time(conic, after_t + wait, arrival_radius, kind);
+        plan.burns.pop();
+        plan.delta_v = plan.burns.iter().map(|burn| burn.prograde.abs()).sum();
+        Some(plan)
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::test_hierarchy;
+
+    const EPSILON: f64 = 0.000001;
+    macro_rules! assert_f {
+        ($x:expr, $y:expr) => {
+            if !(($x - $y) / $y < EPSILON && ($y - $x) / $y < EPSILON) {
+                panic!("assert_f failed: {} !=> {}", $x, $y);
+            }
+        }
+    }
+
+    // conic after each burn of a plan in turn
+    fn execute(
+        conic: &OrbitConic,
+        plan: &TransferPlan,
+    ) -> OrbitConic {
+        plan.burns.iter().fold(*conic, |conic, burn| burn.apply(&conic))
+    }
+
+    #[test]
+    fn test_hohmann_transfer() {
+        let mu = 398600.;
+        let test_oc = OrbitConic::from_elements(7000., 0., 0., 0., 0., 0., mu / G, DVec3::Z);
+        let plan = TransferPlan::to_radius(&test_oc, 42164., TransferKind::Hohmann, 0.).unwrap();
+        assert_eq!(plan.burns.len(), 2);
+
+        // textbook burns from the vis-viva equation
+        let a = (7000. + 42164.) / 2.;
+        let dv1 = f64::sqrt(mu * (2. / 7000. - 1. / a)) - f64::sqrt(mu / 7000.);
+        let dv2 = f64::sqrt(mu / 42164.) - f64::sqrt(mu * (2. / 42164. - 1. / a));
+        assert_f!(plan.burns[0].prograde, dv1);
+        assert_f!(plan.burns[1].prograde, dv2);
+        assert_f!(plan.delta_v, dv1 + dv2);
+        assert_f!(plan.transfer_time, PI * f64::sqrt(a.powi(3) / mu));
+
+        // ends on the target circular orbit
+        let final_oc = execute(&test_oc, &plan);
+        assert!(final_oc.e < 1e-6);
+        assert_f!(final_oc.semi_major_axis(), 42164.);
+
+        // lowering burns retrograde
+        let plan = TransferPlan::to_radius(&final_oc, 7000., TransferKind::Hohmann, plan.burns[1].t).unwrap();
+        assert!(plan.burns.iter().all(|burn| burn.prograde < 0.));
+        assert_f!(execute(&final_oc, &plan).semi_major_axis(), 7000.);
+
+        // open conics have no transfer
+        let open_oc = OrbitConic::from_initial(DVec3::new(7000., 0., 0.), DVec3::new(0., 12., 0.), mu / G, DVec3::Z);
+        assert!(TransferPlan::to_radius(&open_oc, 42164., TransferKind::Hohmann, 0.).is_none());
+    }
+
+    #[test]
+    fn test_bi_elliptic_transfer() {
+        let test_oc = OrbitConic::from_elements(7000., 0., 0., 0., 0., 0., 398600. / G, DVec3::Z);
+        let hohmann = TransferPlan::to_radius(&test_oc, 140000., TransferKind::Hohmann, 0.).unwrap();
+        let bi_elliptic = TransferPlan::to_radius(&test_oc, 140000., TransferKind::BiElliptic(280000.), 0.).unwrap();
+        assert_eq!(bi_elliptic.burns.len(), 3);
+
+        // cheaper than hohmann for a radius ratio of 20, but slower
+        assert!(bi_elliptic.delta_v < hohmann.delta_v);
+        assert!(bi_elliptic.transfer_time > hohmann.transfer_time);
+        let final_oc = execute(&test_oc, &bi_elliptic);
+        assert!(final_oc.e < 1e-6);
+        assert_f!(final_oc.semi_major_axis(), 140000.);
+    }
+
+    #[test]
+    fn test_transfer_to_body() {
+        let hierarchy = test_hierarchy();
+        let test_oc = hierarchy.conic_about(1, 0., DVec3::new(0., 1e4, 0.), DVec3::new(-f64::sqrt(1e16 / 1e4), 0., 0.));
+
+        // arrives beside the moon, outside its orbit
+        for kind in [TransferKind::Hohmann, TransferKind::BiElliptic(2e5)] {
+            let plan = hierarchy.plan_transfer_to_body(1, &test_oc, 2, kind, 100., 10.).unwrap();
+            assert!(plan.burns[0].t >= 10.);
+            let arrival_oc = execute(&test_oc, &plan);
+            let t_arrival = plan.burns[0].t + plan.transfer_time;
+            let moon_pos = hierarchy.body_state_at(2, t_arrival).0;
+            let miss = arrival_oc.pos_at_t(t_arrival) - moon_pos;
+            assert!(miss.length() > 100. && miss.dot(moon_pos) > 0.);
+
+            // passes the moon near the periapsis offset once within its soi, well above its surface
+            let (t_entry, body) = hierarchy.next_soi_transition(1, &arrival_oc, plan.burns[0].t, t_arrival).unwrap();
+            assert_eq!(body, 2);
+            let (pos, vel) = hierarchy.change_frame(1, 2, t_entry, arrival_oc.pos_at_t(t_entry), arrival_oc.vel_at_t(t_entry));
+            let flyby_oc = hierarchy.conic_about(2, t_entry, pos, vel);
+            assert!(f64::abs(flyby_oc.periapsis_radius() / 100. - 1.) < 0.15);
+        }
+
+        // only children of the orbited body
+        assert!(hierarchy.plan_transfer_to_body(2, &test_oc, 1, TransferKind::Hohmann, 100., 0.).is_none());
+    }
+}